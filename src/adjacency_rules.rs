@@ -1,28 +1,66 @@
-use crate::data::{direction, id::Id, tile::Tile};
+use crate::data::{
+    direction, id::Id, mode::Mode, symmetry::Transform, tile::Tile, vector2::Vector2,
+};
 use crate::model::Model;
 
-//  Representation of the adjacency and direction between tile ids.
+///  Representation of the adjacency and direction between tile ids.
 pub struct AdjacencyRules {
+    /// the relative positions that rules are kept for. The first four are always the
+    /// neighbors in `direction::ALL` order, so `offsets[direction.idx()]` is the
+    /// offset of `direction`.
+    pub offsets: Vec<Vector2>,
+    /// `rules[a][b][k]`: whether 'b' may be placed at `offsets[k]` from 'a'
     pub rules: Vec<Vec<Vec<bool>>>,
 }
 
 impl AdjacencyRules {
     pub fn new(num_tiles: usize) -> AdjacencyRules {
+        AdjacencyRules::with_offsets(num_tiles, vec![])
+    }
+
+    /// Rules for the four neighbors plus every offset in `extra_offsets`.
+    pub fn with_offsets(num_tiles: usize, extra_offsets: Vec<Vector2>) -> AdjacencyRules {
+        let offsets = direction::ALL
+            .map(|direction| Vector2 { x: 0, y: 0 }.in_direction(direction))
+            .into_iter()
+            .chain(extra_offsets)
+            .collect::<Vec<Vector2>>();
+
         AdjacencyRules {
-            rules: vec![vec![vec![false, false, false, false]; num_tiles]; num_tiles],
+            rules: vec![vec![vec![false; offsets.len()]; num_tiles]; num_tiles],
+            offsets,
+        }
+    }
+
+    /// The rules for whichever mode `model` was built in.
+    pub fn from_model(model: &Model) -> AdjacencyRules {
+        match model.mode {
+            Mode::Overlap => AdjacencyRules::from_overlap_model(model),
+            Mode::Tile => AdjacencyRules::from_tile_model(model),
+            Mode::TileSet => AdjacencyRules::from_tile_set_model(model),
         }
     }
 
+    /// Patterns constrain each other wherever they overlap, which is any offset of
+    /// less than a tile in both axes, not just the four neighbors.
     pub fn from_overlap_model(model: &Model) -> AdjacencyRules {
         let num_tiles = model.id_to_tile.keys().len();
-        let mut rules = AdjacencyRules::new(num_tiles);
+        let (tile_width, tile_height) = (
+            model.tile_dimensions.0 as i32,
+            model.tile_dimensions.1 as i32,
+        );
+        let extra_offsets = (1 - tile_height..tile_height)
+            .flat_map(|y| (1 - tile_width..tile_width).map(move |x| Vector2 { x, y }))
+            .filter(|offset| offset.x.abs() + offset.y.abs() > 1)
+            .collect();
+        let mut rules = AdjacencyRules::with_offsets(num_tiles, extra_offsets);
 
         let tiles: Vec<&Tile> = model.tile_to_id.keys().collect();
         for a in &tiles {
             for b in &tiles {
-                for direction in direction::ALL {
-                    if a.overlaps(b, direction, model.mode) {
-                        rules.allow(model.tile_to_id[&a], model.tile_to_id[&b], direction);
+                for (k, offset) in rules.offsets.clone().into_iter().enumerate() {
+                    if a.agrees(b, offset) {
+                        rules.rules[model.tile_to_id[a]][model.tile_to_id[b]][k] = true;
                     }
                 }
             }
@@ -31,21 +69,29 @@ impl AdjacencyRules {
         rules
     }
 
-    // I would like to support two tiled models:
-    // 1) from a pre-built world
-    // 2) from a raw tile set
-    //
-    // At time of writing, only the former is supported.
-    //
-    // The tiled model differs from the overlap model greatly in the first model, and
-    // only slightly in the second. For the first, we need to know the original
-    // positions of the tiles so that we can compare their neighbors. This is the pick
-    // up the "artistic intent" of the world design. In the second model, we still want
-    // to do an overlap comparison, but only if the sides share the one-pixel-thick
-    // edge.
+    /// I would like to support two tiled models:
+    /// 1) from a pre-built world
+    /// 2) from a raw tile set
+    ///
+    /// The former is built here, the latter by `from_tile_set_model`.
+    ///
+    /// The tiled model differs from the overlap model greatly in the first model, and
+    /// only slightly in the second. For the first, we need to know the original
+    /// positions of the tiles so that we can compare their neighbors. This is the pick
+    /// up the "artistic intent" of the world design. In the second model, we still want
+    /// to do an overlap comparison, but only if the sides share the one-pixel-thick
+    /// edge.
+    ///
+    /// `position_to_tile` only holds the tiles as they are in the input. Each of their
+    /// variations gets the same neighbors, transformed along with it: if 'b' is to the
+    /// right of 'a', then a quarter turn of 'b' is below a quarter turn of 'a'.
     pub fn from_tile_model(model: &Model) -> AdjacencyRules {
         let num_tiles = model.id_to_tile.keys().len();
         let mut rules = AdjacencyRules::new(num_tiles);
+        let transforms = model
+            .symmetry
+            .transforms_for(model.tile_dimensions)
+            .collect::<Vec<Transform>>();
 
         for (position, tile) in &model.position_to_tile {
             direction::ALL
@@ -57,22 +103,40 @@ impl AdjacencyRules {
                         .map(|tile| (d, tile))
                 })
                 .for_each(|(direction, neighbor)| {
-                    rules.allow(
-                        model.tile_to_id[tile],
-                        model.tile_to_id[neighbor],
-                        *direction,
-                    )
+                    for transform in &transforms {
+                        rules.allow(
+                            model.tile_to_id[&tile.transform(*transform)],
+                            model.tile_to_id[&neighbor.transform(*transform)],
+                            transform.apply(*direction),
+                        )
+                    }
                 });
         }
 
         rules
     }
 
-    pub fn allow(&mut self, a: Id, b: Id, direction: direction::Direction) {
-        self.rules[a][b][direction.idx()] = true;
+    /// Tiles from a raw tile set may be placed next to each other when the
+    /// one-pixel-thick edges that would touch are the same. Variations are compared
+    /// like any other tile, so they need no special handling.
+    pub fn from_tile_set_model(model: &Model) -> AdjacencyRules {
+        let num_tiles = model.id_to_tile.keys().len();
+        let mut rules = AdjacencyRules::new(num_tiles);
+
+        for (a, tile_a) in &model.id_to_tile {
+            for (b, tile_b) in &model.id_to_tile {
+                for direction in direction::ALL {
+                    if tile_a.compare_tile(direction, tile_b) {
+                        rules.allow(*a, *b, direction);
+                    }
+                }
+            }
+        }
+
+        rules
     }
 
-    pub fn valid_neighbors(&self, a: Id, b: Id, direction: direction::Direction) -> bool {
-        self.rules[a][b][direction.idx()]
+    pub fn allow(&mut self, a: Id, b: Id, direction: direction::Direction) {
+        self.rules[a][b][direction.idx()] = true;
     }
 }
@@ -0,0 +1,130 @@
+use crate::data::{cell_state::CellState, vector2::Vector2};
+
+/// Decides which cell is collapsed next: of the cells that aren't collapsed yet, the
+/// one with the lowest priority. A cell's priority is computed again every time it
+/// loses a choice.
+pub trait CellSelector {
+    /// The value the cell at `coord` of a wave of `dimensions` is ranked by.
+    fn priority(&self, coord: Vector2, cell_state: &CellState, dimensions: (u32, u32)) -> f64;
+}
+
+/// The cell with the fewest remaining choices.
+pub struct MinimumRemainingValues;
+
+impl CellSelector for MinimumRemainingValues {
+    fn priority(&self, _coord: Vector2, cell_state: &CellState, _dimensions: (u32, u32)) -> f64 {
+        cell_state.choices.len() as f64
+    }
+}
+
+/// The cell with the lowest Shannon entropy, weighing each choice by how often it
+/// occurs in the input.
+pub struct Entropy;
+
+impl CellSelector for Entropy {
+    fn priority(&self, _coord: Vector2, cell_state: &CellState, _dimensions: (u32, u32)) -> f64 {
+        cell_state.entropy() + cell_state.noise
+    }
+}
+
+/// Row by row, from the top left.
+pub struct Scanline;
+
+impl CellSelector for Scanline {
+    fn priority(&self, coord: Vector2, _cell_state: &CellState, dimensions: (u32, u32)) -> f64 {
+        (coord.y as f64) * (dimensions.0 as f64) + coord.x as f64
+    }
+}
+
+/// Ring by ring outwards from the center, clockwise around each ring.
+pub struct Spiral;
+
+impl CellSelector for Spiral {
+    fn priority(&self, coord: Vector2, _cell_state: &CellState, dimensions: (u32, u32)) -> f64 {
+        let dx = coord.x as f64 - (dimensions.0 as f64 - 1.0) / 2.0;
+        let dy = coord.y as f64 - (dimensions.1 as f64 - 1.0) / 2.0;
+        // counted in half cells, as the center falls between cells along an axis of
+        // even length. That way rings are whole numbers apart, even when only one axis
+        // is even.
+        let ring = (2.0 * dx).abs().max((2.0 * dy).abs()).round();
+        // the angle from the center, from 0 up to but not including 1, so this only
+        // orders the cells within a ring.
+        let turn = dy.atan2(dx).rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU;
+        ring + turn * 0.999
+    }
+}
+
+/// Any cell, in an order that only depends on the seed.
+pub struct Random;
+
+impl CellSelector for Random {
+    fn priority(&self, _coord: Vector2, cell_state: &CellState, _dimensions: (u32, u32)) -> f64 {
+        // drawn once per cell and attempt, see `ENTROPY_NOISE`
+        cell_state.noise
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{bitset::BitSet, cell_state::CellState, grid::Grid, vector2::Vector2};
+
+    use super::{CellSelector, Scanline, Spiral};
+
+    // Every coordinate of a wave of `dimensions`, lowest priority first.
+    fn order(selector: &dyn CellSelector, dimensions: (u32, u32)) -> Vec<Vector2> {
+        let cell_state = CellState::new(BitSet::full(1), &[1.0], vec![], 0.0);
+        let mut coords = Grid::from_fn(dimensions, |coord| coord)
+            .iter()
+            .map(|(coord, _)| coord)
+            .collect::<Vec<Vector2>>();
+        coords.sort_by(|a, b| {
+            let priority = |coord| selector.priority(coord, &cell_state, dimensions);
+            priority(*a).total_cmp(&priority(*b))
+        });
+        coords
+    }
+
+    #[test]
+    fn test_scanline_goes_row_by_row() {
+        let coords = order(&Scanline, (3, 2));
+        assert_eq!(
+            coords.iter().map(|c| (c.x, c.y)).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]
+        );
+    }
+
+    #[test]
+    fn test_spiral_starts_in_the_center() {
+        let coords = order(&Spiral, (5, 5));
+        assert_eq!(coords[0], Vector2 { x: 2, y: 2 });
+        // then the ring around it, clockwise from the right
+        assert_eq!(
+            coords[1..9].iter().map(|c| (c.x, c.y)).collect::<Vec<_>>(),
+            vec![
+                (3, 2),
+                (3, 3),
+                (2, 3),
+                (1, 3),
+                (1, 2),
+                (1, 1),
+                (2, 1),
+                (3, 1)
+            ]
+        );
+        // and the outer ring last
+        assert!(coords[9..]
+            .iter()
+            .all(|c| c.x == 0 || c.y == 0 || c.x == 4 || c.y == 4));
+    }
+
+    #[test]
+    fn test_spiral_keeps_rings_apart_when_only_one_side_is_even() {
+        let coords = order(&Spiral, (4, 3));
+        assert_eq!(
+            coords[..6].iter().map(|c| (c.x, c.y)).collect::<Vec<_>>(),
+            vec![(2, 1), (1, 1), (2, 2), (1, 2), (1, 0), (2, 0)]
+        );
+        // the left and right columns are the outer ring
+        assert!(coords[6..].iter().all(|c| c.x == 0 || c.x == 3));
+    }
+}
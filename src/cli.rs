@@ -2,30 +2,205 @@ use std::str::FromStr;
 use std::{self, path::PathBuf};
 
 use anyhow::{anyhow, Ok, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use wfc_rs::{
+    cell_selector::{CellSelector, Entropy, MinimumRemainingValues, Random, Scanline, Spiral},
+    data::symmetry::Symmetry,
+    tile_selector::{MostFrequent, Temperature, TileSelector, UniformRandom, WeightedRandom},
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 /// Run wfc-rs
 pub struct Args {
-    /// Input location. If running in 'tiled set' mode, this is assumed to be a directory.
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// learn a model and its adjacency rules from an input, and save them so that
+    /// `generate --model` can skip learning
+    Learn(LearnArgs),
+    /// generate an output image from an input, or from a model saved by `learn`
+    Generate(GenerateArgs),
+}
+
+#[derive(clap::Args)]
+pub struct LearnArgs {
+    /// Input location. If running in 'tile' or 'tile-set' mode, this may also be a
+    /// directory of same-sized tile images. Each one is named after its file, and an
+    /// optional `<name>.toml` next to it can set its `weight` and `symmetry`. If running
+    /// in 'rules' mode, this is the rules file.
     pub input: PathBuf,
 
+    /// file to save the learned model to
+    #[clap(long)]
+    pub model: PathBuf,
+
+    #[clap(flatten)]
+    pub variations: VariationArgs,
+
+    #[clap(subcommand)]
+    pub mode: Mode,
+}
+
+#[derive(clap::Args)]
+pub struct GenerateArgs {
+    /// Input location, see `learn --help`. Not needed with `--model`.
+    #[clap(required_unless_present = "model", conflicts_with = "model")]
+    pub input: Option<PathBuf>,
+
+    /// a model saved by `learn`, used instead of learning from an input
+    #[clap(long)]
+    pub model: Option<PathBuf>,
+
     /// Output dimensions. If running in 'tiled' mode, then this is the number of tiles.
     /// If in 'overlap' mode, then it is in pixels.
     #[clap(short, long, value_parser = parse_tuple_arg)]
     pub output_dimensions: (u32, u32),
 
     #[clap(subcommand)]
-    pub mode: Mode,
+    pub mode: Option<Mode>,
+
+    #[clap(flatten)]
+    pub variations: VariationArgs,
 
     /// whether or not to make a gif (warning: very slow)
     #[clap(long)]
     pub make_gif: bool,
 
-    /// whether or not create all variations (rotations and reflections) of tiles
-    #[clap(short, long)]
+    /// how many collapse decisions to remember so that a contradiction can be undone
+    /// instead of failing the run. 0 disables backtracking
+    #[clap(long, default_value_t = 0)]
+    pub backtrack_depth: usize,
+
+    /// how many times to restart from scratch when a contradiction can't be resolved
+    #[clap(long, default_value_t = 10)]
+    pub max_attempts: usize,
+
+    /// seed for the random number generator. The same input, options and seed always
+    /// produce the same output. A random seed is picked (and printed) if omitted
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// how to pick the next cell to collapse
+    #[clap(long, value_enum, default_value = "entropy")]
+    pub heuristic: Heuristic,
+
+    /// how to pick the tile a cell collapses into
+    #[clap(long, value_enum, default_value = "weighted")]
+    pub tile_selector: TileSelection,
+
+    /// pick tiles by their weights in the input, sharpened or flattened by this
+    /// temperature instead of by `--tile-selector`. Below 1 favors common tiles even
+    /// more than the input does, and above 1 gives rare tiles more of a chance
+    #[clap(long, conflicts_with = "tile-selector", value_parser = parse_temperature)]
+    pub temperature: Option<f64>,
+
+    /// wrap the output around both edges so that it tiles seamlessly
+    #[clap(long)]
+    pub periodic_output: bool,
+
+    /// wrap the output around its left and right edges only
+    #[clap(long)]
+    pub periodic_output_x: bool,
+
+    /// wrap the output around its top and bottom edges only
+    #[clap(long)]
+    pub periodic_output_y: bool,
+}
+
+impl GenerateArgs {
+    // Whether the output wraps horizontally and vertically.
+    pub fn periodic_output(&self) -> (bool, bool) {
+        (
+            self.periodic_output || self.periodic_output_x,
+            self.periodic_output || self.periodic_output_y,
+        )
+    }
+
+    pub fn tile_selector(&self) -> Box<dyn TileSelector> {
+        match self.temperature {
+            Some(temperature) => Box::new(Temperature { temperature }),
+            None => self.tile_selector.tile_selector(),
+        }
+    }
+}
+
+// Which variations of the input tiles to learn from. A saved model already holds its
+// variations, so these don't apply to `generate --model`.
+#[derive(clap::Args)]
+pub struct VariationArgs {
+    /// whether or not create all variations (rotations and reflections) of tiles. Same
+    /// as `--symmetry all`
+    #[clap(short, long, conflicts_with = "symmetry")]
     pub with_tile_variations: bool,
+
+    /// which variations (rotations and reflections) of tiles to create. Non-square tiles
+    /// can't be turned a quarter, so they only get reflected and turned a half
+    #[clap(long, value_enum, default_value = "none")]
+    pub symmetry: Symmetry,
+}
+
+impl VariationArgs {
+    pub fn symmetry(&self) -> Symmetry {
+        match self.with_tile_variations {
+            true => Symmetry::All,
+            false => self.symmetry,
+        }
+    }
+}
+
+// The built-in ways to pick the next cell to collapse, see `CellSelector`.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Heuristic {
+    // the cell with the lowest weighted Shannon entropy
+    Entropy,
+    // the cell with the fewest remaining choices
+    #[clap(name = "mrv")]
+    MinimumRemainingValues,
+    // row by row, from the top left
+    Scanline,
+    // outwards from the center
+    Spiral,
+    // any cell, in an order picked by the seed
+    Random,
+}
+
+impl Heuristic {
+    pub fn cell_selector(self) -> Box<dyn CellSelector> {
+        match self {
+            Heuristic::Entropy => Box::new(Entropy),
+            Heuristic::MinimumRemainingValues => Box::new(MinimumRemainingValues),
+            Heuristic::Scanline => Box::new(Scanline),
+            Heuristic::Spiral => Box::new(Spiral),
+            Heuristic::Random => Box::new(Random),
+        }
+    }
+}
+
+// The built-in ways to pick the tile a cell collapses into, see `TileSelector`.
+// `Temperature` is left out, as it is picked by giving a temperature.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TileSelection {
+    // as often as each tile occurs in the input
+    Weighted,
+    // every tile equally often
+    Uniform,
+    // always the most common tile
+    MostFrequent,
+}
+
+impl TileSelection {
+    pub fn tile_selector(self) -> Box<dyn TileSelector> {
+        match self {
+            TileSelection::Weighted => Box::new(WeightedRandom),
+            TileSelection::Uniform => Box::new(UniformRandom),
+            TileSelection::MostFrequent => Box::new(MostFrequent),
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -34,12 +209,31 @@ pub enum Mode {
         /// tile dimensions to parse from input image
         #[clap(short, long, value_parser = parse_tuple_arg)]
         tile_dimensions: (u32, u32),
+
+        /// treat the input as tileable, sampling patterns that wrap around its edges
+        #[clap(long)]
+        periodic_input: bool,
     },
     Tile {
         /// tile dimensions to parse from input image
         #[clap(short, value_parser = parse_tuple_arg)]
         tile_dimensions: (u32, u32),
     },
+    TileSet {
+        /// tile dimensions to slice the input sheet by. Not needed when the input is a
+        /// directory of tiles
+        #[clap(short, long, value_parser = parse_tuple_arg)]
+        tile_dimensions: Option<(u32, u32)>,
+    },
+    /// read the tiles and which of them may touch from a TOML or JSON rules file
+    Rules,
+}
+
+fn parse_temperature(value: &str) -> Result<f64> {
+    match f64::from_str(value) {
+        std::result::Result::Ok(temperature) if temperature > 0.0 => Ok(temperature),
+        _ => Err(anyhow!("Temperature must be a number above 0: {}", value)),
+    }
 }
 
 fn parse_tuple_arg(value: &str) -> Result<(u32, u32)> {
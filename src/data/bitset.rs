@@ -0,0 +1,186 @@
+use crate::data::id::Id;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Fixed-size set of tile ids, one bit per id. Used as the domain of a cell: every
+/// set bit is a tile that can still be placed there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    size: usize,
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// A set that can hold ids `0..size`, with none of them in it.
+    pub fn new(size: usize) -> BitSet {
+        BitSet {
+            size,
+            words: vec![0; size.div_ceil(WORD_BITS)],
+        }
+    }
+
+    /// A set that can hold ids `0..size`, with all of them in it.
+    pub fn full(size: usize) -> BitSet {
+        let mut set = BitSet {
+            size,
+            words: vec![u64::MAX; size.div_ceil(WORD_BITS)],
+        };
+        // clear the unused bits of the last word so that `len` stays correct
+        let remainder = size % WORD_BITS;
+        if let (Some(last), true) = (set.words.last_mut(), remainder != 0) {
+            *last = (1 << remainder) - 1;
+        }
+        set
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn insert(&mut self, id: Id) {
+        self.words[id / WORD_BITS] |= 1 << (id % WORD_BITS);
+    }
+
+    /// Returns whether `id` was in the set.
+    pub fn remove(&mut self, id: Id) -> bool {
+        let contained = self.contains(id);
+        self.words[id / WORD_BITS] &= !(1 << (id % WORD_BITS));
+        contained
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.words[id / WORD_BITS] & (1 << (id % WORD_BITS)) != 0
+    }
+
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|word| *word = 0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Keep only the ids that are also in `other`.
+    pub fn intersect(&mut self, other: &BitSet) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word &= other_word;
+        }
+    }
+
+    /// Add every id in `other`.
+    pub fn union(&mut self, other: &BitSet) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word |= other_word;
+        }
+    }
+
+    /// The ids that are in this set but not in `other`.
+    pub fn difference(&self, other: &BitSet) -> BitSet {
+        BitSet {
+            size: self.size,
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(word, other_word)| word & !other_word)
+                .collect(),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            word_idx: 0,
+            current: self.words.first().copied().unwrap_or(0),
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    words: &'a [u64],
+    word_idx: usize,
+    // the bits of `words[word_idx]` that haven't been yielded yet
+    current: u64,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        while self.current == 0 {
+            self.word_idx += 1;
+            self.current = *self.words.get(self.word_idx)?;
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        // clear the lowest set bit
+        self.current &= self.current - 1;
+        Some(self.word_idx * WORD_BITS + bit)
+    }
+}
+
+impl<'a> IntoIterator for &'a BitSet {
+    type Item = Id;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitSet;
+
+    #[test]
+    fn test_full() {
+        for size in [1, 63, 64, 65, 130] {
+            let set = BitSet::full(size);
+            assert_eq!(set.len(), size);
+            assert_eq!(
+                set.iter().collect::<Vec<_>>(),
+                (0..size).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut set = BitSet::new(100);
+        assert!(set.is_empty());
+
+        set.insert(3);
+        set.insert(64);
+        set.insert(99);
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(64));
+        assert!(!set.contains(63));
+
+        assert!(set.remove(64));
+        assert!(!set.remove(64));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 99]);
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let mut a = BitSet::new(70);
+        let mut b = BitSet::new(70);
+        [1, 5, 65].iter().for_each(|id| a.insert(*id));
+        [5, 65, 69].iter().for_each(|id| b.insert(*id));
+
+        assert_eq!(a.difference(&b).iter().collect::<Vec<_>>(), vec![1]);
+
+        let mut union = a.clone();
+        union.union(&b);
+        assert_eq!(union.iter().collect::<Vec<_>>(), vec![1, 5, 65, 69]);
+
+        a.intersect(&b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![5, 65]);
+    }
+}
@@ -1,24 +1,109 @@
-use crate::data::id::Id;
+use crate::data::{bitset::BitSet, id::Id};
 
+#[derive(Clone)]
 pub struct CellState {
-    pub choices: Vec<Id>,
+    /// tiles that can still be placed in this cell. Once collapsed, just `state`.
+    pub choices: BitSet,
     pub state: Option<Id>,
+    /// Running sums over the remaining choices, kept up to date by `remove_choice` so
+    /// that the entropy doesn't need to be recomputed from scratch.
+    pub sum_of_weights: f64,
+    pub sum_of_weight_log_weights: f64,
+    /// Tiny random offset added to the entropy so that ties are broken randomly.
+    pub noise: f64,
+    /// For every tile and offset, how many choices left in the cell at the opposite
+    /// offset allow that tile here. Once a count drops to zero, the tile can be
+    /// removed. Counts are kept for removed tiles too, so that a removal can be undone
+    /// by adding back what it took away. See: `Propagator::support_idx`.
+    pub support: Vec<u16>,
 }
 
 impl CellState {
+    pub fn new(choices: BitSet, weights: &[f64], support: Vec<u16>, noise: f64) -> CellState {
+        let sum_of_weights = choices.iter().map(|id| weights[id]).sum();
+        let sum_of_weight_log_weights = choices
+            .iter()
+            .map(|id| weights[id] * weights[id].ln())
+            .sum();
+
+        CellState {
+            choices,
+            state: None,
+            sum_of_weights,
+            sum_of_weight_log_weights,
+            noise,
+            support,
+        }
+    }
+
+    pub fn collapse(&mut self, choice: Id) {
+        self.choices.clear();
+        self.choices.insert(choice);
+        self.state = Some(choice);
+    }
+
     pub fn is_collapsed(&self) -> bool {
         self.state.is_some()
     }
 
-    pub fn get_choices(&self) -> Vec<Id> {
-        self.state
-            .map(|id| vec![id])
-            .unwrap_or(self.choices.clone())
+    /// Shannon entropy of the remaining choices, weighted by `weights`:
+    /// H = log(sum(w)) - sum(w * log(w)) / sum(w)
+    pub fn entropy(&self) -> f64 {
+        self.sum_of_weights.ln() - self.sum_of_weight_log_weights / self.sum_of_weights
     }
 
-    pub fn remove_choice(&mut self, choice: &Id) {
-        if let Some(idx) = self.choices.iter().position(|id| id == choice) {
-            self.choices.remove(idx);
+    pub fn remove_choice(&mut self, choice: Id, weight: f64) {
+        if self.choices.remove(choice) {
+            self.sum_of_weights -= weight;
+            self.sum_of_weight_log_weights -= weight * weight.ln();
         }
     }
+
+    /// Undo `remove_choice`.
+    pub fn add_choice(&mut self, choice: Id, weight: f64) {
+        if !self.choices.contains(choice) {
+            self.choices.insert(choice);
+            self.sum_of_weights += weight;
+            self.sum_of_weight_log_weights += weight * weight.ln();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::bitset::BitSet;
+
+    use super::CellState;
+
+    fn mk_choices(ids: &[usize], size: usize) -> BitSet {
+        let mut choices = BitSet::new(size);
+        ids.iter().for_each(|id| choices.insert(*id));
+        choices
+    }
+
+    #[test]
+    fn test_entropy_of_equal_weights() {
+        let cell_state = CellState::new(BitSet::full(4), &[2.0, 2.0, 2.0, 2.0], vec![], 0.0);
+        assert!((cell_state.entropy() - 4f64.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_remove_choice_updates_entropy() {
+        let weights = [9.0, 1.0, 5.0];
+        let mut cell_state = CellState::new(BitSet::full(3), &weights, vec![], 0.0);
+        cell_state.remove_choice(2, weights[2]);
+
+        let expected = CellState::new(mk_choices(&[0, 1], 3), &weights, vec![], 0.0);
+        assert_eq!(cell_state.choices, expected.choices);
+        assert!((cell_state.entropy() - expected.entropy()).abs() < 1e-9);
+
+        // a heavily skewed cell is more certain than an even one
+        let even = CellState::new(BitSet::full(2), &[1.0, 1.0], vec![], 0.0);
+        assert!(cell_state.entropy() < even.entropy());
+
+        cell_state.add_choice(2, weights[2]);
+        let full = CellState::new(BitSet::full(3), &weights, vec![], 0.0);
+        assert_eq!(cell_state.choices, full.choices);
+        assert!((cell_state.entropy() - full.entropy()).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,259 @@
+use std::ops::{Index, IndexMut};
+
+use crate::data::vector2::Vector2;
+
+/// Dense, row-major 2D storage. Coordinates map straight to an index into `cells`, so
+/// lookups never hash anything.
+#[derive(Clone)]
+pub struct Grid<T> {
+    width: u32,
+    height: u32,
+    // whether neighbors wrap around the left/right and top/bottom edges
+    periodic: (bool, bool),
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn from_fn(dimensions: (u32, u32), mut f: impl FnMut(Vector2) -> T) -> Grid<T> {
+        let (width, height) = dimensions;
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                f(Vector2 {
+                    x: x as i32,
+                    y: y as i32,
+                })
+            })
+            .collect();
+
+        Grid {
+            width,
+            height,
+            periodic: (false, false),
+            cells,
+        }
+    }
+
+    /// Make `at_offset` wrap around the edges: horizontally if `periodic.0`, vertically
+    /// if `periodic.1`. With both, the grid is a torus.
+    pub fn with_periodic(mut self, periodic: (bool, bool)) -> Grid<T> {
+        self.periodic = periodic;
+        self
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn idx(&self, coord: Vector2) -> usize {
+        (coord.y as usize) * (self.width as usize) + (coord.x as usize)
+    }
+
+    /// Inverse of `idx`.
+    pub fn coord(&self, idx: usize) -> Vector2 {
+        let width = self.width as usize;
+        Vector2 {
+            x: (idx % width) as i32,
+            y: (idx / width) as i32,
+        }
+    }
+
+    /// Every cell along with its coordinate, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (Vector2, &T)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| (self.coord(idx), cell))
+    }
+
+    /// Every cell, mutably, in row-major order.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    /// The coordinate at `offset` from `coord`, or `None` if it falls off the grid.
+    pub fn at_offset(&self, coord: Vector2, offset: Vector2) -> Option<Vector2> {
+        at_offset(self.dimensions(), self.periodic, coord, offset)
+    }
+}
+
+// Along a periodic axis, coordinates past one edge wrap around to the other instead
+// of falling off.
+fn at_offset(
+    dimensions: (u32, u32),
+    periodic: (bool, bool),
+    coord: Vector2,
+    offset: Vector2,
+) -> Option<Vector2> {
+    let wrap = |value: i32, size: u32, periodic: bool| match periodic {
+        true => Some(value.rem_euclid(size as i32)),
+        false => (0..size as i32).contains(&value).then_some(value),
+    };
+    let Vector2 { x, y } = coord + offset;
+
+    Some(Vector2 {
+        x: wrap(x, dimensions.0, periodic.0)?,
+        y: wrap(y, dimensions.1, periodic.1)?,
+    })
+}
+
+impl<T> Index<Vector2> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, coord: Vector2) -> &T {
+        &self.cells[self.idx(coord)]
+    }
+}
+
+impl<T> IndexMut<Vector2> for Grid<T> {
+    fn index_mut(&mut self, coord: Vector2) -> &mut T {
+        let idx = self.idx(coord);
+        &mut self.cells[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{direction::Direction, vector2::Vector2};
+
+    use super::Grid;
+
+    // The neighbors of `coord` paired with the direction they are in.
+    fn neighbors(grid: &Grid<()>, coord: Vector2) -> Vec<(Vector2, Direction)> {
+        [
+            Direction::UP,
+            Direction::LEFT,
+            Direction::RIGHT,
+            Direction::DOWN,
+        ]
+        .into_iter()
+        .filter_map(|direction| {
+            let offset = Vector2 { x: 0, y: 0 }.in_direction(direction);
+            grid.at_offset(coord, offset)
+                .map(|neighbor| (neighbor, direction))
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let grid = Grid::from_fn((3, 3), |_| ());
+
+        let cases = vec![
+            (
+                Vector2 { x: 0, y: 0 },
+                vec![
+                    (Vector2 { x: 1, y: 0 }, Direction::RIGHT),
+                    (Vector2 { x: 0, y: 1 }, Direction::DOWN),
+                ],
+            ),
+            (
+                Vector2 { x: 1, y: 0 },
+                vec![
+                    (Vector2 { x: 0, y: 0 }, Direction::LEFT),
+                    (Vector2 { x: 2, y: 0 }, Direction::RIGHT),
+                    (Vector2 { x: 1, y: 1 }, Direction::DOWN),
+                ],
+            ),
+            (
+                Vector2 { x: 2, y: 0 },
+                vec![
+                    (Vector2 { x: 1, y: 0 }, Direction::LEFT),
+                    (Vector2 { x: 2, y: 1 }, Direction::DOWN),
+                ],
+            ),
+            (
+                Vector2 { x: 0, y: 1 },
+                vec![
+                    (Vector2 { x: 0, y: 0 }, Direction::UP),
+                    (Vector2 { x: 1, y: 1 }, Direction::RIGHT),
+                    (Vector2 { x: 0, y: 2 }, Direction::DOWN),
+                ],
+            ),
+            (
+                Vector2 { x: 1, y: 1 },
+                vec![
+                    (Vector2 { x: 1, y: 0 }, Direction::UP),
+                    (Vector2 { x: 0, y: 1 }, Direction::LEFT),
+                    (Vector2 { x: 2, y: 1 }, Direction::RIGHT),
+                    (Vector2 { x: 1, y: 2 }, Direction::DOWN),
+                ],
+            ),
+            (
+                Vector2 { x: 2, y: 1 },
+                vec![
+                    (Vector2 { x: 2, y: 0 }, Direction::UP),
+                    (Vector2 { x: 1, y: 1 }, Direction::LEFT),
+                    (Vector2 { x: 2, y: 2 }, Direction::DOWN),
+                ],
+            ),
+            (
+                Vector2 { x: 0, y: 2 },
+                vec![
+                    (Vector2 { x: 0, y: 1 }, Direction::UP),
+                    (Vector2 { x: 1, y: 2 }, Direction::RIGHT),
+                ],
+            ),
+            (
+                Vector2 { x: 1, y: 2 },
+                vec![
+                    (Vector2 { x: 1, y: 1 }, Direction::UP),
+                    (Vector2 { x: 0, y: 2 }, Direction::LEFT),
+                    (Vector2 { x: 2, y: 2 }, Direction::RIGHT),
+                ],
+            ),
+            (
+                Vector2 { x: 2, y: 2 },
+                vec![
+                    (Vector2 { x: 2, y: 1 }, Direction::UP),
+                    (Vector2 { x: 1, y: 2 }, Direction::LEFT),
+                ],
+            ),
+        ];
+
+        for (pos, expected) in cases {
+            assert_eq!(neighbors(&grid, pos), expected)
+        }
+    }
+
+    #[test]
+    fn test_periodic_neighbors() {
+        let corner = Vector2 { x: 0, y: 0 };
+
+        let grid = Grid::from_fn((3, 2), |_| ()).with_periodic((true, false));
+        assert_eq!(
+            neighbors(&grid, corner),
+            vec![
+                (Vector2 { x: 2, y: 0 }, Direction::LEFT),
+                (Vector2 { x: 1, y: 0 }, Direction::RIGHT),
+                (Vector2 { x: 0, y: 1 }, Direction::DOWN),
+            ]
+        );
+
+        let grid = Grid::from_fn((3, 2), |_| ()).with_periodic((true, true));
+        assert_eq!(
+            neighbors(&grid, corner),
+            vec![
+                (Vector2 { x: 0, y: 1 }, Direction::UP),
+                (Vector2 { x: 2, y: 0 }, Direction::LEFT),
+                (Vector2 { x: 1, y: 0 }, Direction::RIGHT),
+                (Vector2 { x: 0, y: 1 }, Direction::DOWN),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        let grid = Grid::from_fn((3, 2), |coord| coord.y * 10 + coord.x);
+
+        assert_eq!(grid[Vector2 { x: 2, y: 1 }], 12);
+        assert_eq!(
+            grid.coord(grid.idx(Vector2 { x: 2, y: 1 })),
+            Vector2 { x: 2, y: 1 }
+        );
+        assert_eq!(
+            grid.iter().map(|(_, cell)| *cell).collect::<Vec<_>>(),
+            vec![0, 1, 2, 10, 11, 12]
+        );
+    }
+}
@@ -1,7 +1,10 @@
+pub mod bitset;
 pub mod cell_state;
 pub mod color;
 pub mod direction;
+pub mod grid;
 pub mod id;
 pub mod mode;
+pub mod symmetry;
 pub mod tile;
 pub mod vector2;
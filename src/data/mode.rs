@@ -1,5 +1,11 @@
-#[derive(Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Mode {
     Overlap,
+    // learned from a pre-built world
     Tile,
+    // a raw tile set, matched by edges or by a rules file
+    TileSet,
 }
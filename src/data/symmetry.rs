@@ -0,0 +1,202 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{
+    direction::{self, Direction},
+    vector2::Vector2,
+};
+
+/// Which rotations and reflections of the input tiles are added as variations.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Symmetry {
+    // the tiles as they are in the input
+    None,
+    // mirrored left to right
+    #[clap(name = "hflip")]
+    #[serde(rename = "hflip")]
+    HorizontalFlip,
+    // mirrored top to bottom
+    #[clap(name = "vflip")]
+    #[serde(rename = "vflip")]
+    VerticalFlip,
+    // turned a quarter, half and three quarters
+    Rotations,
+    // every rotation and reflection
+    All,
+}
+
+impl Symmetry {
+    pub fn transforms(self) -> &'static [Transform] {
+        match self {
+            Symmetry::None => &[Transform::Identity],
+            Symmetry::HorizontalFlip => &[Transform::Identity, Transform::FlipHorizontal],
+            Symmetry::VerticalFlip => &[Transform::Identity, Transform::FlipVertical],
+            Symmetry::Rotations => &[
+                Transform::Identity,
+                Transform::Rotate90,
+                Transform::Rotate180,
+                Transform::Rotate270,
+            ],
+            Symmetry::All => &Transform::ALL,
+        }
+    }
+
+    /// The transforms that can be applied to a tile of `tile_dimensions`. A quarter
+    /// turn or diagonal flip swaps the width and height, so a non-square tile
+    /// transformed that way no longer fits the grid.
+    pub fn transforms_for(self, tile_dimensions: (u32, u32)) -> impl Iterator<Item = Transform> {
+        let square = tile_dimensions.0 == tile_dimensions.1;
+        self.transforms()
+            .iter()
+            .copied()
+            .filter(move |transform| square || !transform.swaps_dimensions())
+    }
+}
+
+/// One of the eight rotations and reflections of a square (the dihedral group D4).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+    Identity,
+    // clockwise
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // mirrored left to right
+    FlipHorizontal,
+    // mirrored top to bottom
+    FlipVertical,
+    // mirrored along the top left to bottom right diagonal
+    Transpose,
+    // mirrored along the top right to bottom left diagonal
+    AntiTranspose,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipHorizontal,
+        Transform::FlipVertical,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    /// Where `direction` points to after the transform. Transforming two neighboring
+    /// tiles and the direction between them together keeps them neighbors.
+    pub fn apply(self, direction: Direction) -> Direction {
+        let origin = Vector2 { x: 0, y: 0 };
+        let Vector2 { x, y } = origin.in_direction(direction);
+        // y grows downwards, so a clockwise quarter turn takes up (0, -1) to right (1, 0)
+        let (x, y) = match self {
+            Transform::Identity => (x, y),
+            Transform::Rotate90 => (-y, x),
+            Transform::Rotate180 => (-x, -y),
+            Transform::Rotate270 => (y, -x),
+            Transform::FlipHorizontal => (-x, y),
+            Transform::FlipVertical => (x, -y),
+            Transform::Transpose => (y, x),
+            Transform::AntiTranspose => (-y, -x),
+        };
+
+        direction::ALL
+            .into_iter()
+            .find(|direction| origin.in_direction(*direction) == Vector2 { x, y })
+            .unwrap()
+    }
+
+    /// Whether the width and height swap places.
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(
+            self,
+            Transform::Rotate90
+                | Transform::Rotate270
+                | Transform::Transpose
+                | Transform::AntiTranspose
+        )
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Transform::Identity => "identity",
+            Transform::Rotate90 => "rotate-90",
+            Transform::Rotate180 => "rotate-180",
+            Transform::Rotate270 => "rotate-270",
+            Transform::FlipHorizontal => "hflip",
+            Transform::FlipVertical => "vflip",
+            Transform::Transpose => "transpose",
+            Transform::AntiTranspose => "anti-transpose",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Which transforms leave a tile unchanged, named after the letter with the same
+/// symmetry (the names used by the original WaveFunctionCollapse tilesets).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymmetryClass {
+    // unchanged by every transform
+    X,
+    // unchanged by the half turn and both straight flips
+    I,
+    // unchanged by the half turn and both diagonal flips
+    Backslash,
+    // unchanged by every rotation, but by no flip
+    Pinwheel,
+    // unchanged by one straight flip
+    T,
+    // unchanged by one diagonal flip
+    L,
+    // unchanged by the half turn only
+    S,
+    // only unchanged by the identity
+    F,
+}
+
+impl SymmetryClass {
+    /// The class of a tile that is left unchanged by exactly the transforms in
+    /// `invariant`.
+    pub fn from_invariant_transforms(invariant: &[Transform]) -> SymmetryClass {
+        let has = |transform| invariant.contains(&transform);
+
+        if has(Transform::Rotate90) && has(Transform::FlipHorizontal) {
+            SymmetryClass::X
+        } else if has(Transform::Rotate90) {
+            SymmetryClass::Pinwheel
+        } else if has(Transform::FlipHorizontal) && has(Transform::FlipVertical) {
+            SymmetryClass::I
+        } else if has(Transform::Transpose) && has(Transform::AntiTranspose) {
+            SymmetryClass::Backslash
+        } else if has(Transform::FlipHorizontal) || has(Transform::FlipVertical) {
+            SymmetryClass::T
+        } else if has(Transform::Transpose) || has(Transform::AntiTranspose) {
+            SymmetryClass::L
+        } else if has(Transform::Rotate180) {
+            SymmetryClass::S
+        } else {
+            SymmetryClass::F
+        }
+    }
+}
+
+impl fmt::Display for SymmetryClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SymmetryClass::X => "X",
+            SymmetryClass::I => "I",
+            SymmetryClass::Backslash => "\\",
+            SymmetryClass::Pinwheel => "pinwheel",
+            SymmetryClass::T => "T",
+            SymmetryClass::L => "L",
+            SymmetryClass::S => "S",
+            SymmetryClass::F => "F",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -1,8 +1,13 @@
-use crate::data::{color::RGB, direction::Direction, mode::Mode};
+use crate::data::{
+    color::RGB,
+    direction::Direction,
+    symmetry::{Symmetry, SymmetryClass, Transform},
+    vector2::Vector2,
+};
 
 use super::color::Color;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Tile {
     pub width: u32,
     pub height: u32,
@@ -24,55 +29,68 @@ impl Tile {
     }
 
     pub fn at(&self, x: u32, y: u32) -> RGB {
-        self.pixels[self.get_idx(x, y)].clone()
+        self.pixels[self.get_idx(x, y)]
     }
 
-    pub fn overlaps(&self, other: &Tile, dir: Direction, mode: Mode) -> bool {
-        match mode {
-            Mode::Tile => self.compare_tile(dir, other),
-            Mode::Overlap => self.compare_overlap(dir, other),
+    pub fn transform(&self, transform: Transform) -> Tile {
+        let tile = self.clone();
+        match transform {
+            Transform::Identity => tile,
+            Transform::Rotate90 => rotate_90(tile),
+            Transform::Rotate180 => rotate_180(tile),
+            Transform::Rotate270 => rotate_270(tile),
+            Transform::FlipHorizontal => flip_horizontal(tile),
+            Transform::FlipVertical => flip_vertical(tile),
+            Transform::Transpose => flip_horizontal(rotate_90(tile)),
+            Transform::AntiTranspose => flip_vertical(rotate_90(tile)),
         }
     }
 
-    pub fn permute(&self) -> Vec<Tile> {
-        let mut permutations = vec![self.clone()];
-        permutations.push(rotate_90(self.clone()));
-        permutations.push(rotate_180(self.clone()));
-        permutations.push(rotate_270(self.clone()));
-        permutations.push(flip_horizontal(self.clone()));
-        permutations.push(flip_vertical(self.clone()));
-        permutations.push(flip_vertical(rotate_90(self.clone())));
-        permutations.push(flip_horizontal(rotate_90(self.clone())));
-        permutations
+    /// The distinct tiles produced by the transforms in `symmetry`, starting with the
+    /// tile itself. A symmetric tile maps onto itself under some of them, and those
+    /// copies are only kept once. Non-square tiles only get the variations that keep
+    /// their shape, see `Symmetry::transforms_for`.
+    pub fn variations(&self, symmetry: Symmetry) -> Vec<Tile> {
+        let mut variations: Vec<Tile> = vec![];
+        for transform in symmetry.transforms_for((self.width, self.height)) {
+            let variation = self.transform(transform);
+            if !variations.contains(&variation) {
+                variations.push(variation);
+            }
+        }
+        variations
+    }
+
+    pub fn symmetry_class(&self) -> SymmetryClass {
+        let invariant = Transform::ALL
+            .into_iter()
+            .filter(|transform| self.transform(*transform) == *self)
+            .collect::<Vec<Transform>>();
+
+        SymmetryClass::from_invariant_transforms(&invariant)
     }
 
     fn get_idx(&self, x: u32, y: u32) -> usize {
         ((self.width * y) + x) as usize
     }
 
-    fn compare_overlap(&self, dir: Direction, other: &Tile) -> bool {
-        let (ys, xs, f_x, f_y) = match dir {
-            // compare top-edge of 'a' to bottom-edge of 'b'
-            Direction::UP => (1..self.height, 0..self.width, 0, -1),
-            // compare bottom-edge of 'a' to top-edge of 'b'
-            Direction::DOWN => (0..self.height - 1, 0..self.width, 0, 1),
-            // compare left-edge of 'a' to right-edge of 'b'
-            Direction::LEFT => (0..self.height, 1..self.width, -1, 0),
-            // compare right-side of 'a' to left-side of 'b'
-            Direction::RIGHT => (0..self.height, 0..self.width - 1, 1, 0),
-        };
-
-        for y in ys {
-            for x in xs.clone() {
-                if self.at((x as i32 + f_x) as u32, (y as i32 + f_y) as u32) != other.at(x, y) {
-                    return false;
-                }
-            }
-        }
-        true
+    /// Whether 'b' can be placed with its top left pixel at `offset` from the top left
+    /// of 'a': every pixel where the two tiles overlap must be the same color.
+    pub fn agrees(&self, other: &Tile, offset: Vector2) -> bool {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let xs = offset.x.max(0)..(width + offset.x).min(width);
+        let ys = offset.y.max(0)..(height + offset.y).min(height);
+
+        ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .all(|(x, y)| {
+                self.at(x as u32, y as u32)
+                    == other.at((x - offset.x) as u32, (y - offset.y) as u32)
+            })
     }
 
-    fn compare_tile(&self, dir: Direction, other: &Tile) -> bool {
+    /// Whether the edges of 'a' and 'b' that would touch if 'b' was placed in `dir` of
+    /// 'a' are the same.
+    pub fn compare_tile(&self, dir: Direction, other: &Tile) -> bool {
         match dir {
             // compare top-edge of 'a' to bottom-edge of 'b'
             Direction::UP => {
@@ -135,30 +153,17 @@ impl Tile {
     }
 }
 
-// papa bless: https://stackoverflow.com/a/8664879/6147439
+// Clockwise. The width and height swap places.
 fn rotate_90(tile: Tile) -> Tile {
-    let chunked = tile
-        .pixels
-        .chunks(tile.width as usize)
-        .map(|c| c.to_vec())
-        .collect::<Vec<Vec<RGB>>>();
-
-    let transposed = transpose(tile.width as usize, chunked);
-    let new_pixels = transposed
-        .iter()
-        .flat_map(|row: &Vec<RGB>| {
-            row.iter()
-                .rev()
-                .map(|c| c.clone())
-                .collect::<Vec<RGB>>()
-                .clone()
-        })
+    let pixels = (0..tile.width)
+        .flat_map(|y| (0..tile.height).map(move |x| (x, y)))
+        .map(|(x, y)| tile.at(y, tile.height - 1 - x))
         .collect();
 
     Tile {
-        width: tile.width,
-        height: tile.height,
-        pixels: new_pixels,
+        width: tile.height,
+        height: tile.width,
+        pixels,
     }
 }
 
@@ -166,7 +171,7 @@ fn rotate_180(tile: Tile) -> Tile {
     let new_pixels = tile
         .pixels
         .chunks(tile.width as usize)
-        .map(|row| row.iter().rev().map(|c| c.clone()))
+        .map(|row| row.iter().rev().copied())
         .rev()
         .flatten()
         .collect::<Vec<RGB>>();
@@ -178,24 +183,17 @@ fn rotate_180(tile: Tile) -> Tile {
     }
 }
 
+// Counter-clockwise. The width and height swap places.
 fn rotate_270(tile: Tile) -> Tile {
-    let chunked = tile
-        .pixels
-        .chunks(tile.width as usize)
-        .map(|c| c.to_vec())
-        .collect::<Vec<Vec<RGB>>>();
-
-    let transposed = transpose(tile.width as usize, chunked);
-    let new_pixels = transposed
-        .iter()
-        .rev()
-        .flat_map(|row: &Vec<RGB>| row.iter().map(|c| c.clone()).collect::<Vec<RGB>>().clone())
+    let pixels = (0..tile.width)
+        .flat_map(|y| (0..tile.height).map(move |x| (x, y)))
+        .map(|(x, y)| tile.at(tile.width - 1 - y, x))
         .collect();
 
     Tile {
-        width: tile.width,
-        height: tile.height,
-        pixels: new_pixels,
+        width: tile.height,
+        height: tile.width,
+        pixels,
     }
 }
 
@@ -203,7 +201,7 @@ fn flip_horizontal(tile: Tile) -> Tile {
     let new_pixels = tile
         .pixels
         .chunks(tile.width as usize)
-        .flat_map(|row| row.iter().rev().map(|c| c.clone()))
+        .flat_map(|row| row.iter().rev().copied())
         .collect();
 
     Tile {
@@ -217,7 +215,7 @@ fn flip_vertical(tile: Tile) -> Tile {
         .pixels
         .chunks(tile.width as usize)
         .rev()
-        .flat_map(|row| row.iter().map(|c| c.clone()))
+        .flat_map(|row| row.iter().copied())
         .collect();
 
     Tile {
@@ -227,16 +225,6 @@ fn flip_vertical(tile: Tile) -> Tile {
     }
 }
 
-fn transpose(width: usize, xs: Vec<Vec<RGB>>) -> Vec<Vec<RGB>> {
-    let mut rotated = vec![vec![[0, 0, 0]; xs[0].len()]; xs.len()];
-    for i in 0..(width as usize) {
-        for j in 0..(width as usize) {
-            rotated[i][j] = xs[j][i];
-        }
-    }
-    rotated
-}
-
 #[cfg(test)]
 mod tests {
     use crate::data::{color::BLACK, tile::*};
@@ -352,6 +340,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rotate_non_square() {
+        // 0 1 2
+        // 3 4 5
+        let tile = Tile {
+            width: 3,
+            height: 2,
+            pixels: (0..6).map(|i| [i, i, i]).collect(),
+        };
+
+        let rotated = rotate_90(tile.clone());
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(
+            rotated.pixels,
+            vec![[3; 3], [0; 3], [4; 3], [1; 3], [5; 3], [2; 3]]
+        );
+
+        let rotated = rotate_270(tile.clone());
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(
+            rotated.pixels,
+            vec![[2; 3], [5; 3], [1; 3], [4; 3], [0; 3], [3; 3]]
+        );
+
+        assert_eq!(rotate_90(rotate_270(tile.clone())), tile);
+
+        // only shape-preserving variations for non-square tiles
+        let variations = tile.variations(Symmetry::All);
+        assert_eq!(variations.len(), 4);
+        assert!(variations
+            .iter()
+            .all(|t| (t.width, t.height) == (3, 2) && t.pixels.len() == 6));
+    }
+
+    #[test]
+    fn test_variations_follow_symmetry() {
+        // 0 1
+        // 2 3
+        let tile = Tile {
+            width: 2,
+            height: 2,
+            pixels: (0..4).map(|i| [i, i, i]).collect(),
+        };
+        let variations = |symmetry| tile.variations(symmetry);
+
+        assert_eq!(variations(Symmetry::None), vec![tile.clone()]);
+        assert_eq!(
+            variations(Symmetry::HorizontalFlip),
+            vec![tile.clone(), flip_horizontal(tile.clone())]
+        );
+        assert_eq!(
+            variations(Symmetry::VerticalFlip),
+            vec![tile.clone(), flip_vertical(tile.clone())]
+        );
+        // side-view friendly: the bottom row never ends up on top
+        assert!(variations(Symmetry::HorizontalFlip)
+            .iter()
+            .all(|t| t.pixels[2..].iter().all(|p| p[0] >= 2)));
+        assert_eq!(variations(Symmetry::Rotations).len(), 4);
+        assert_eq!(variations(Symmetry::All).len(), 8);
+    }
+
+    #[test]
+    fn test_transforms_move_directions_with_pixels() {
+        let tile = Tile {
+            width: 3,
+            height: 3,
+            pixels: (0..9).map(|i| [i, i, i]).collect(),
+        };
+        let center = Vector2 { x: 1, y: 1 };
+        let at = |tile: &Tile, Vector2 { x, y }| tile.at(x as u32, y as u32);
+
+        // the pixel next to the center in `direction` ends up next to the center in
+        // `transform.apply(direction)`
+        for transform in Transform::ALL {
+            let transformed = tile.transform(transform);
+            for direction in crate::data::direction::ALL {
+                assert_eq!(
+                    at(&tile, center.in_direction(direction)),
+                    at(
+                        &transformed,
+                        center.in_direction(transform.apply(direction))
+                    )
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_compare_tile() {
         let a = Tile {
@@ -373,10 +449,10 @@ mod tests {
             ],
         };
 
-        assert!(a.overlaps(&b, Direction::UP, Mode::Tile));
-        assert!(!a.overlaps(&b, Direction::DOWN, Mode::Tile));
-        assert!(!a.overlaps(&b, Direction::LEFT, Mode::Tile));
-        assert!(!a.overlaps(&b, Direction::RIGHT, Mode::Tile));
+        assert!(a.compare_tile(Direction::UP, &b));
+        assert!(!a.compare_tile(Direction::DOWN, &b));
+        assert!(!a.compare_tile(Direction::LEFT, &b));
+        assert!(!a.compare_tile(Direction::RIGHT, &b));
     }
 
     #[test]
@@ -400,7 +476,7 @@ mod tests {
             ],
         };
 
-        assert!(a.overlaps(&b, Direction::UP, Mode::Overlap))
+        assert!(a.agrees(&b, Vector2 { x: 0, y: 0 }.in_direction(Direction::UP)))
     }
 
     #[test]
@@ -424,7 +500,7 @@ mod tests {
             ],
         };
 
-        assert!(a.overlaps(&b, Direction::DOWN, Mode::Overlap))
+        assert!(a.agrees(&b, Vector2 { x: 0, y: 0 }.in_direction(Direction::DOWN)))
     }
 
     #[test]
@@ -448,7 +524,7 @@ mod tests {
             ],
         };
 
-        assert!(a.overlaps(&b, Direction::LEFT, Mode::Overlap))
+        assert!(a.agrees(&b, Vector2 { x: 0, y: 0 }.in_direction(Direction::LEFT)))
     }
 
     #[test]
@@ -468,6 +544,35 @@ mod tests {
             ],
         };
 
-        assert!(a.overlaps(&b, Direction::RIGHT, Mode::Overlap))
+        assert!(a.agrees(&b, Vector2 { x: 0, y: 0 }.in_direction(Direction::RIGHT)))
+    }
+
+    #[test]
+    fn test_agrees_at_diagonal_offsets() {
+        let a = Tile {
+            width: 3,
+            height: 3,
+            pixels: vec![
+                BLACK, BLACK, BLACK, //
+                BLACK, BLACK, BLACK, //
+                BLACK, BLACK, WHITE,
+            ],
+        };
+        let b = Tile {
+            width: 3,
+            height: 3,
+            pixels: vec![
+                WHITE, BLACK, BLACK, //
+                BLACK, BLACK, BLACK, //
+                BLACK, BLACK, BLACK,
+            ],
+        };
+
+        // only the bottom right pixel of 'a' overlaps the top left pixel of 'b'
+        assert!(a.agrees(&b, Vector2 { x: 2, y: 2 }));
+        assert!(b.agrees(&a, Vector2 { x: -2, y: -2 }));
+        assert!(!a.agrees(&b, Vector2 { x: 1, y: 1 }));
+        // tiles that don't overlap at all always agree
+        assert!(a.agrees(&b, Vector2 { x: 3, y: 0 }));
     }
 }
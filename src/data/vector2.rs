@@ -1,4 +1,4 @@
-use std::ops::Add;
+use std::ops::{Add, Neg};
 
 use super::direction::Direction;
 
@@ -19,6 +19,17 @@ impl Vector2 {
     }
 }
 
+impl Neg for Vector2 {
+    type Output = Vector2;
+
+    fn neg(self) -> Vector2 {
+        Vector2 {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
 impl Add for Vector2 {
     type Output = Vector2;
 
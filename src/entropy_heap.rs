@@ -0,0 +1,88 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::data::vector2::Vector2;
+
+// Min-heap of cells keyed by their entropy. Entries are never updated in place: when
+// a cell's entropy changes a new entry is pushed and the old one is left behind, so
+// whoever pops is responsible for skipping the stale ones.
+pub struct EntropyHeap {
+    heap: BinaryHeap<Entry>,
+}
+
+#[derive(PartialEq)]
+struct Entry {
+    entropy: f64,
+    coord: Vector2,
+}
+
+impl Eq for Entry {}
+
+impl Ord for Entry {
+    // `BinaryHeap` is a max-heap, so the comparison is reversed to pop the lowest
+    // entropy first. Ties are broken by position (top-left first) to stay
+    // deterministic.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .entropy
+            .total_cmp(&self.entropy)
+            .then_with(|| (other.coord.y, other.coord.x).cmp(&(self.coord.y, self.coord.x)))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl EntropyHeap {
+    pub fn new() -> EntropyHeap {
+        EntropyHeap {
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    pub fn push(&mut self, coord: Vector2, entropy: f64) {
+        self.heap.push(Entry { entropy, coord });
+    }
+
+    pub fn pop(&mut self) -> Option<(Vector2, f64)> {
+        self.heap.pop().map(|entry| (entry.coord, entry.entropy))
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::vector2::Vector2;
+
+    use super::EntropyHeap;
+
+    #[test]
+    fn test_pops_lowest_entropy_first() {
+        let mut heap = EntropyHeap::new();
+        heap.push(Vector2 { x: 0, y: 0 }, 2.5);
+        heap.push(Vector2 { x: 1, y: 0 }, 0.5);
+        heap.push(Vector2 { x: 2, y: 0 }, 1.5);
+
+        assert_eq!(heap.pop(), Some((Vector2 { x: 1, y: 0 }, 0.5)));
+        assert_eq!(heap.pop(), Some((Vector2 { x: 2, y: 0 }, 1.5)));
+        assert_eq!(heap.pop(), Some((Vector2 { x: 0, y: 0 }, 2.5)));
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn test_ties_pop_in_reading_order() {
+        let mut heap = EntropyHeap::new();
+        heap.push(Vector2 { x: 0, y: 1 }, 1.0);
+        heap.push(Vector2 { x: 1, y: 0 }, 1.0);
+        heap.push(Vector2 { x: 0, y: 0 }, 1.0);
+
+        assert_eq!(heap.pop(), Some((Vector2 { x: 0, y: 0 }, 1.0)));
+        assert_eq!(heap.pop(), Some((Vector2 { x: 1, y: 0 }, 1.0)));
+        assert_eq!(heap.pop(), Some((Vector2 { x: 0, y: 1 }, 1.0)));
+    }
+}
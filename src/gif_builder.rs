@@ -1,17 +1,44 @@
+use crate::data::{id::Id, vector2::Vector2};
 use crate::image::Image;
+use crate::observer::Observer;
+use crate::wave_function::{Solution, WaveFunction};
 use anyhow::{anyhow, Result};
 use gif::{Frame, Repeat};
-use std::fs::File;
+use std::{fs::File, path::Path};
 
 const FRAME_SPEED: i32 = 1;
+// Rendering a snapshot everytime a cell collapses is visually uninteresting (no one
+// likes to watch a 40 second gif). To save space and time, we only keep one every
+// `GIF_SIZE_FACTOR` collapses.
+const GIF_SIZE_FACTOR: usize = 10;
+
+/// Keeps an image of the wave every `GIF_SIZE_FACTOR` collapses and once it is
+/// finished, to be turned into a GIF with `GifBuilder::make_gif`. The count is per
+/// recorder, so waves collapsed side by side don't skew each other's frames.
+#[derive(Default)]
+pub struct GifRecorder {
+    pub snapshots: Vec<Image>,
+    collapses: usize,
+}
+
+impl Observer for GifRecorder {
+    fn on_collapse(&mut self, wave_function: &WaveFunction, _coord: Vector2, _tile: Id) {
+        if self.collapses.is_multiple_of(GIF_SIZE_FACTOR) {
+            self.snapshots.push(wave_function.to_image());
+        }
+        self.collapses += 1;
+    }
+
+    fn on_complete(&mut self, wave_function: &WaveFunction, _solution: &Solution) {
+        self.snapshots.push(wave_function.to_image());
+    }
+}
 
 pub struct GifBuilder;
 
 impl GifBuilder {
-    pub fn make_gif(images: &Vec<Image>) -> Result<()> {
-        println!("Generating gif with {} frames.", images.len());
-
-        match &images[..] {
+    pub fn make_gif(images: &[Image], path: &Path) -> Result<()> {
+        match images {
             [first, rest @ ..] => {
                 let width = first.width as u16;
                 let height = first.height as u16;
@@ -20,19 +47,19 @@ impl GifBuilder {
                     |pixels: Vec<u8>| Frame::from_rgb_speed(width, height, &pixels, FRAME_SPEED);
 
                 // Get pixel data from some source
-                let pixels: Vec<u8> = first.pixels.iter().flat_map(|f| f.clone()).collect();
+                let pixels: Vec<u8> = first.pixels.iter().flat_map(|f| *f).collect();
 
                 // Create first frame from data
                 let frame = make_frame(pixels);
 
                 // Create encoder
-                let mut image = File::create("output.gif")?;
+                let mut image = File::create(path)?;
                 let mut encoder = gif::Encoder::new(&mut image, frame.width, frame.height, &[])?;
                 encoder.set_repeat(Repeat::Infinite)?;
                 encoder.write_frame(&frame)?;
 
                 for image in rest {
-                    let pixels: Vec<u8> = image.pixels.iter().flat_map(|f| f.clone()).collect();
+                    let pixels: Vec<u8> = image.pixels.iter().flat_map(|f| *f).collect();
                     let frame = make_frame(pixels);
                     encoder.write_frame(&frame)?;
                 }
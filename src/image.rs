@@ -55,9 +55,7 @@ impl Image {
             for x in 0..width {
                 let idx = get_position(x, y, width, bytes_per_color) as usize;
                 let mut color: RGB = [0; 3];
-                for i in 0..3 {
-                    color[i] = bytes[idx + i];
-                }
+                color.copy_from_slice(&bytes[idx..idx + 3]);
                 pixels.push(color);
             }
         }
@@ -73,14 +71,14 @@ impl Image {
         let file_name = format!("{}.png", path);
         let path = Path::new(&file_name);
         let file = File::create(path)?;
-        let ref mut w = BufWriter::new(file);
+        let w = &mut BufWriter::new(file);
 
         let mut encoder = png::Encoder::new(w, self.width, self.height);
         encoder.set_color(png::ColorType::Rgb);
         let mut writer = encoder.write_header()?;
 
-        let buf: Vec<u8> = self.pixels.iter().flat_map(|c| c.clone()).collect();
-        writer.write_image_data(&buf.as_slice())?;
+        let buf: Vec<u8> = self.pixels.iter().flat_map(|c| *c).collect();
+        writer.write_image_data(buf.as_slice())?;
 
         Ok(())
     }
@@ -0,0 +1,67 @@
+//! Wave function collapse: learn which tiles may sit next to each other from an
+//! example image, then generate new images that follow the same rules.
+//!
+//! A [`Model`] holds the tiles and how often they occur, and [`AdjacencyRules`] which
+//! of them may be placed at which offsets from each other. A [`WaveFunction`] collapses
+//! a grid of cells into a [`Solution`] that obeys those rules.
+//!
+//! ```
+//! use wfc_rs::{
+//!     data::{symmetry::Symmetry, vector2::Vector2},
+//!     AdjacencyRules, Image, Model, WaveFunction, WaveFunctionOptions,
+//! };
+//!
+//! // a checkerboard, which only allows checkerboards
+//! let mut input = Image::new(4, 4);
+//! for y in 0..4 {
+//!     for x in 0..4 {
+//!         if (x + y) % 2 == 0 {
+//!             input.set_color(Vector2 { x, y }, [255, 255, 255]);
+//!         }
+//!     }
+//! }
+//!
+//! let model = Model::overlap_from_image(&input, (2, 2), Symmetry::None, true)?;
+//! let adjacency_rules = AdjacencyRules::from_model(&model);
+//! let wave_dimensions = model.wave_dimensions((8, 8), (false, false))?;
+//!
+//! let mut wave_function = WaveFunction::new(
+//!     wave_dimensions,
+//!     adjacency_rules,
+//!     model,
+//!     WaveFunctionOptions {
+//!         seed: 7,
+//!         ..WaveFunctionOptions::default()
+//!     },
+//! );
+//! let solution = wave_function.run()?;
+//!
+//! assert_eq!(solution.tile_ids.dimensions(), (7, 7));
+//! assert_eq!((solution.image.width, solution.image.height), (8, 8));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod adjacency_rules;
+pub mod cell_selector;
+pub mod data;
+mod entropy_heap;
+pub mod gif_builder;
+pub mod image;
+pub mod model;
+pub mod model_file;
+pub mod observer;
+mod propagator;
+pub mod rules_file;
+pub mod tile_selector;
+pub mod wave_function;
+
+pub use adjacency_rules::AdjacencyRules;
+pub use cell_selector::CellSelector;
+pub use image::Image;
+pub use model::Model;
+pub use observer::Observer;
+pub use tile_selector::TileSelector;
+pub use wave_function::{
+    Event, Events, Solution, WaveFunction, WaveFunctionError, WaveFunctionOptions,
+};
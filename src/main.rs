@@ -1,48 +1,130 @@
-use anyhow::Result;
+use std::{collections::BTreeMap, path::Path, path::PathBuf};
+
+use anyhow::{anyhow, Result};
 use clap::Parser;
 
-use adjacency_rules::AdjacencyRules;
-use model::Model;
-use wave_function::WaveFunction;
+use wfc_rs::{
+    data::symmetry::Symmetry,
+    gif_builder::{GifBuilder, GifRecorder},
+    model_file,
+    observer::ConsoleProgress,
+    rules_file, AdjacencyRules, Model, WaveFunction, WaveFunctionOptions,
+};
 
-mod adjacency_rules;
 mod cli;
-mod data;
-mod gif_builder;
-mod helpers;
-mod image;
-mod model;
-mod unique_stack;
-mod wave_function;
 
 fn main() -> Result<()> {
-    let args: cli::Args = cli::Args::parse();
-
-    let (model, adjacency_rules) = match args.mode {
-        cli::Mode::Overlap { tile_dimensions } => {
-            let model = Model::overlap(args.input, tile_dimensions, args.with_tile_variations)?;
-            let adjacency_rules = AdjacencyRules::from_overlap_model(&model);
-            (model, adjacency_rules)
+    match cli::Args::parse().command {
+        cli::Command::Learn(args) => {
+            let (model, adjacency_rules) =
+                learn(args.input, args.mode, args.variations.symmetry())?;
+            print_model(&model);
+            model_file::save_model(&args.model, &model, &adjacency_rules)?;
+            println!("Model saved to {}", args.model.display());
+            Ok(())
         }
-        cli::Mode::Tile { tile_dimensions } => {
-            let model = Model::tiled(args.input, tile_dimensions, args.with_tile_variations)?;
-            let adjacency_rules = AdjacencyRules::from_tile_model(&model);
-            (model, adjacency_rules)
+        cli::Command::Generate(args) => generate(args),
+    }
+}
+
+fn generate(mut args: cli::GenerateArgs) -> Result<()> {
+    let periodic_output = args.periodic_output();
+
+    let (model, adjacency_rules) = match (&args.model, args.input.take(), args.mode.take()) {
+        (Some(_), _, Some(_)) => {
+            return Err(anyhow!(
+                "A saved model already knows its mode, so none can be given with --model"
+            ))
         }
+        (Some(path), _, None) => model_file::load_model(path)?,
+        (None, Some(input), Some(mode)) => learn(input, mode, args.variations.symmetry())?,
+        // clap requires either an input or a model
+        (None, _, _) => return Err(anyhow!("A mode is needed to learn from the input")),
     };
+    print_model(&model);
 
-    println!("Unique tiles found: {}", model.id_to_tile.keys().len());
+    let wave_dimensions = model.wave_dimensions(args.output_dimensions, periodic_output)?;
     println!(
         "Grid area to solve: {}",
-        args.output_dimensions.0 * args.output_dimensions.1
+        wave_dimensions.0 * wave_dimensions.1
     );
 
+    let seed = args.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+
     let mut wave_function = WaveFunction::new(
-        args.output_dimensions,
+        wave_dimensions,
         adjacency_rules,
         model,
-        args.make_gif,
+        WaveFunctionOptions {
+            backtrack_depth: args.backtrack_depth,
+            max_attempts: args.max_attempts,
+            seed,
+            cell_selector: args.heuristic.cell_selector(),
+            tile_selector: args.tile_selector(),
+            periodic_output,
+        },
+    );
+
+    let mut gif_recorder = GifRecorder::default();
+    let solution = match args.make_gif {
+        true => wave_function.run_observed(&mut [&mut ConsoleProgress, &mut gif_recorder])?,
+        false => wave_function.run_observed(&mut [&mut ConsoleProgress])?,
+    };
+
+    if args.make_gif {
+        let snapshots = &gif_recorder.snapshots;
+        println!("Generating gif with {} frames.", snapshots.len());
+        GifBuilder::make_gif(snapshots, Path::new("output.gif"))
+    } else {
+        solution.image.save("output")
+    }
+}
+
+fn learn(input: PathBuf, mode: cli::Mode, symmetry: Symmetry) -> Result<(Model, AdjacencyRules)> {
+    Ok(match mode {
+        cli::Mode::Overlap {
+            tile_dimensions,
+            periodic_input,
+        } => with_learned_rules(Model::overlap(
+            input,
+            tile_dimensions,
+            symmetry,
+            periodic_input,
+        )?),
+        cli::Mode::Tile { tile_dimensions } => {
+            with_learned_rules(Model::tiled(input, tile_dimensions, symmetry)?)
+        }
+        cli::Mode::TileSet { tile_dimensions } => {
+            with_learned_rules(Model::tile_set(input, tile_dimensions, symmetry)?)
+        }
+        cli::Mode::Rules => rules_file::read_rules_file(&input, symmetry)?,
+    })
+}
+
+fn print_model(model: &Model) {
+    println!(
+        "Unique tiles found: {} (out of {} sampled)",
+        model.id_to_tile.keys().len(),
+        model.id_to_frequency.values().sum::<i32>()
     );
+    let mut symmetry_classes: BTreeMap<String, usize> = BTreeMap::new();
+    for symmetry in model.id_to_symmetry.values() {
+        *symmetry_classes.entry(symmetry.to_string()).or_default() += 1;
+    }
+    println!(
+        "Tiles by symmetry class: {}",
+        symmetry_classes
+            .iter()
+            .map(|(symmetry, count)| format!("{} {}", count, symmetry))
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+}
 
-    wave_function.run()
+// The rules the tiles of `model` imply. A directory given to tile mode is read as a
+// tile set, so this goes by the mode of the model rather than the command line.
+fn with_learned_rules(model: Model) -> (Model, AdjacencyRules) {
+    let adjacency_rules = AdjacencyRules::from_model(&model);
+    (model, adjacency_rules)
 }
@@ -1,13 +1,20 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
 
 use crate::{
-    data::{id::Id, mode::Mode, tile::Tile, vector2::Vector2},
+    data::{
+        id::Id,
+        mode::Mode,
+        symmetry::{Symmetry, SymmetryClass, Transform},
+        tile::Tile,
+        vector2::Vector2,
+    },
     image::Image,
 };
 
-// Model holds all data relevant to constructing and resolving the wave.
+/// Model holds all data relevant to constructing and resolving the wave.
 #[derive(Clone)]
 pub struct Model {
     pub mode: Mode,
@@ -16,55 +23,47 @@ pub struct Model {
     pub position_to_tile: HashMap<Vector2, Tile>,
     pub id_to_tile: HashMap<Id, Tile>,
     pub tile_to_id: HashMap<Tile, Id>,
+    /// how many times each tile occurs in the input, variations included
+    pub id_to_frequency: HashMap<Id, i32>,
+    /// `id_to_frequency` normalized to sum to 1. Used to weigh the random choices.
     pub frequency_hints: HashMap<Id, f64>,
+    /// which rotations and reflections leave each tile unchanged
+    pub id_to_symmetry: HashMap<Id, SymmetryClass>,
+    /// the rotations and reflections the input tiles were varied by
+    pub symmetry: Symmetry,
+    /// Tiles read from a directory are named after their file, and their variations
+    /// after the file and the transform, e.g. "road rotate-90". Empty otherwise.
+    pub id_to_name: HashMap<Id, String>,
 }
 
 impl Model {
-    // Given an image and a tile size, construct and return the tiles
-    // paired with their frequency of occurance in the input image.
+    /// An overlap model learned from the PNG at `image_path`, see
+    /// `Model::overlap_from_image`.
     pub fn overlap(
         image_path: PathBuf,
         tile_dimensions: (u32, u32),
-        with_tile_variations: bool,
+        symmetry: Symmetry,
+        periodic_input: bool,
     ) -> Result<Model> {
         let image = Image::from_png(image_path)?;
-        let (tile_w, tile_h) = tile_dimensions;
-        let mut tile_to_freq: HashMap<Tile, i32> = HashMap::new();
-
-        for y in 0..image.height {
-            for x in 0..image.width {
-                let mut pixels = vec![];
-                for y_t in y..(y + tile_h) {
-                    for x_t in x..(x + tile_w) {
-                        let pixel = Vector2 {
-                            x: (x_t as i32) % image.width as i32,
-                            y: (y_t as i32) % image.height as i32,
-                        };
-                        let color = image.at(pixel);
-                        pixels.push(color.clone());
-                    }
-                }
-
-                let tile = Tile {
-                    width: tile_w,
-                    height: tile_h,
-                    pixels,
-                };
+        Model::overlap_from_image(&image, tile_dimensions, symmetry, periodic_input)
+    }
 
-                let tiles = if with_tile_variations {
-                    tile.permute() // TODO
-                } else {
-                    vec![tile]
-                };
+    /// Every `tile_dimensions` pattern of `image` and its variations by `symmetry`,
+    /// weighted by how often it occurs. With `periodic_input`, patterns that wrap
+    /// around the edges of the image are sampled too.
+    pub fn overlap_from_image(
+        image: &Image,
+        tile_dimensions: (u32, u32),
+        symmetry: Symmetry,
+        periodic_input: bool,
+    ) -> Result<Model> {
+        let tile_to_freq =
+            overlap_tile_frequencies(image, tile_dimensions, symmetry, periodic_input)?;
 
-                for tile in tiles {
-                    let freq = tile_to_freq.get(&tile).map(|f| f + 1).unwrap_or(1);
-                    tile_to_freq.insert(tile, freq);
-                }
-            }
-        }
         Ok(Model::from_frequency_and_position_maps(
             Mode::Overlap,
+            symmetry,
             tile_to_freq,
             // tiles generated in an overlap mode don't have positions
             HashMap::new(),
@@ -72,18 +71,37 @@ impl Model {
         ))
     }
 
+    /// A tiled model learned from the PNG at `image_path`, see
+    /// `Model::tiled_from_image`. A directory of tiles is read as a tile set instead.
     pub fn tiled(
         image_path: PathBuf,
         tile_dimensions: (u32, u32),
-        with_tile_variations: bool,
+        symmetry: Symmetry,
     ) -> Result<Model> {
+        // A directory holds loose tiles rather than a world to learn neighbors from,
+        // so which tiles fit together can only come from their edges.
+        if image_path.is_dir() {
+            return Model::tile_set(image_path, Some(tile_dimensions), symmetry);
+        }
+
         let image = Image::from_png(image_path)?;
-        let (tile_width, tile_height) = tile_dimensions;
+        Model::tiled_from_image(&image, tile_dimensions, symmetry)
+    }
 
-        assert!(
-            image.width % tile_width == 0 && image.height % tile_height == 0,
-            "Tiled Sheet must be evenly divisible by the tile dimensions."
-        );
+    /// The `tile_dimensions` tiles that `image` is made of, and their variations by
+    /// `symmetry`. Which tiles are next to each other in the image is kept, so that
+    /// `AdjacencyRules::from_tile_model` can allow the same neighbors.
+    pub fn tiled_from_image(
+        image: &Image,
+        tile_dimensions: (u32, u32),
+        symmetry: Symmetry,
+    ) -> Result<Model> {
+        let (tile_width, tile_height) = tile_dimensions;
+        if !image.width.is_multiple_of(tile_width) || !image.height.is_multiple_of(tile_height) {
+            return Err(anyhow!(
+                "Tiled Sheet must be evenly divisible by the tile dimensions."
+            ));
+        }
 
         let mut tile_to_positions: HashMap<Tile, Vec<Vector2>> = HashMap::new();
         let mut position_to_tile: HashMap<Vector2, Tile> = HashMap::new();
@@ -96,29 +114,9 @@ impl Model {
                     x: (x / tile_width) as i32,
                     y: (y / tile_height) as i32,
                 };
-                let mut pixels = vec![];
-
-                for t_y in y..(y + tile_height) {
-                    for t_x in x..(x + tile_width) {
-                        let pixel = Vector2 {
-                            x: (t_x as i32) % image.width as i32,
-                            y: (t_y as i32) % image.height as i32,
-                        };
-                        pixels.push(image.at(pixel));
-                    }
-                }
+                let tile = tile_at(image, position, tile_dimensions);
 
-                let tile = Tile {
-                    width: tile_width,
-                    height: tile_height,
-                    pixels,
-                };
-
-                let tiles = if with_tile_variations {
-                    tile.permute() // TODO
-                } else {
-                    vec![tile.clone()]
-                };
+                let tiles = tile.variations(symmetry);
 
                 for tile in tiles {
                     let freq = tile_to_freq.get(&tile).map(|f| f + 1).unwrap_or(1);
@@ -137,20 +135,169 @@ impl Model {
 
         Ok(Model::from_frequency_and_position_maps(
             Mode::Tile,
+            symmetry,
             tile_to_freq,
             tile_to_positions,
             position_to_tile,
         ))
     }
 
+    /// A raw tile set: every tile of a sheet, or every PNG in a directory. Unlike in
+    /// `Model::tiled`, the tiles don't need to appear next to each other anywhere.
+    /// Which ones fit together is decided by their edges, see
+    /// `AdjacencyRules::from_tile_set_model`.
+    pub fn tile_set(
+        input: PathBuf,
+        tile_dimensions: Option<(u32, u32)>,
+        symmetry: Symmetry,
+    ) -> Result<Model> {
+        let tiles = match tile_dimensions {
+            _ if input.is_dir() => read_tile_directory(&input)?,
+            Some(tile_dimensions) => read_tile_sheet(input.clone(), tile_dimensions)?
+                .into_iter()
+                .map(|tile| NamedTile {
+                    name: None,
+                    tile,
+                    metadata: TileMetadata::default(),
+                })
+                .collect(),
+            None => {
+                return Err(anyhow!(
+                    "Tile dimensions are required to slice a tile sheet: {}",
+                    input.display()
+                ))
+            }
+        };
+
+        if let (Some(expected), Some(named)) = (tile_dimensions, tiles.first()) {
+            if (named.tile.width, named.tile.height) != expected {
+                return Err(anyhow!(
+                    "The tiles in {} are {}x{}, not {}x{}",
+                    input.display(),
+                    named.tile.width,
+                    named.tile.height,
+                    expected.0,
+                    expected.1
+                ));
+            }
+        }
+
+        Ok(Model::from_named_tiles(tiles, symmetry))
+    }
+
+    /// A tile set model over `tiles` and their variations. Each tile's metadata may
+    /// override its weight and `symmetry`.
+    pub fn from_named_tiles(tiles: Vec<NamedTile>, symmetry: Symmetry) -> Model {
+        let mut tile_to_freq: HashMap<Tile, i32> = HashMap::new();
+        let mut tile_to_name: HashMap<Tile, String> = HashMap::new();
+        for NamedTile {
+            name,
+            tile,
+            metadata,
+        } in tiles
+        {
+            let symmetry = metadata.symmetry.unwrap_or(symmetry);
+            for variation in tile.variations(symmetry) {
+                *tile_to_freq.entry(variation.clone()).or_insert(0) += metadata.weight;
+
+                if let Some(name) = &name {
+                    let transform = symmetry
+                        .transforms_for((tile.width, tile.height))
+                        .find(|transform| tile.transform(*transform) == variation)
+                        .unwrap();
+                    let variation_name = match transform {
+                        Transform::Identity => name.clone(),
+                        _ => format!("{} {}", name, transform),
+                    };
+                    // identical tiles keep the first name they were found under
+                    tile_to_name.entry(variation).or_insert(variation_name);
+                }
+            }
+        }
+
+        Model::from_learned_tiles(Mode::TileSet, symmetry, tile_to_freq, tile_to_name)
+    }
+
+    /// A model over tiles whose frequencies are already known, such as a tile set or a
+    /// model saved by `learn`. Where the tiles were found isn't kept, so this can't be
+    /// used to learn the rules of `Mode::Tile`.
+    pub fn from_learned_tiles(
+        mode: Mode,
+        symmetry: Symmetry,
+        tile_to_freq: HashMap<Tile, i32>,
+        tile_to_name: HashMap<Tile, String>,
+    ) -> Model {
+        let mut model = Model::from_frequency_and_position_maps(
+            mode,
+            symmetry,
+            tile_to_freq,
+            HashMap::new(),
+            HashMap::new(),
+        );
+        model.id_to_name = tile_to_name
+            .into_iter()
+            .map(|(tile, name)| (model.tile_to_id[&tile], name))
+            .collect();
+        model
+    }
+
+    /// The size of the wave that renders to an image of `output_dimensions`. In the
+    /// overlap model every cell holds a whole pattern, so along an axis that doesn't
+    /// wrap around, the last pattern already covers the last `N - 1` pixels.
+    pub fn wave_dimensions(
+        &self,
+        output_dimensions: (u32, u32),
+        periodic_output: (bool, bool),
+    ) -> Result<(u32, u32)> {
+        let (width, height) = output_dimensions;
+        let (tile_width, tile_height) = self.tile_dimensions;
+        match self.mode {
+            Mode::Tile | Mode::TileSet => Ok(output_dimensions),
+            Mode::Overlap if width < tile_width || height < tile_height => Err(anyhow!(
+                "Output dimensions ({}, {}) are smaller than the tile dimensions ({}, {})",
+                width,
+                height,
+                tile_width,
+                tile_height
+            )),
+            Mode::Overlap => Ok((
+                shrink(width, tile_width, periodic_output.0),
+                shrink(height, tile_height, periodic_output.1),
+            )),
+        }
+    }
+
+    /// Inverse of `wave_dimensions`.
+    pub fn output_dimensions(
+        &self,
+        wave_dimensions: (u32, u32),
+        periodic_output: (bool, bool),
+    ) -> (u32, u32) {
+        let (width, height) = wave_dimensions;
+        let (tile_width, tile_height) = self.tile_dimensions;
+        match self.mode {
+            Mode::Tile | Mode::TileSet => wave_dimensions,
+            Mode::Overlap => (
+                grow(width, tile_width, periodic_output.0),
+                grow(height, tile_height, periodic_output.1),
+            ),
+        }
+    }
+
     fn from_frequency_and_position_maps(
         mode: Mode,
+        symmetry: Symmetry,
         tile_to_freq: HashMap<Tile, i32>,
         tile_to_positions: HashMap<Tile, Vec<Vector2>>,
         position_to_tile: HashMap<Vector2, Tile>,
     ) -> Model {
-        let tile_to_id: HashMap<Tile, Id> = tile_to_freq
-            .keys()
+        // `HashMap` iteration order changes from run to run, so sort the tiles to
+        // hand out the same ids every time.
+        let mut tiles = tile_to_freq.keys().collect::<Vec<&Tile>>();
+        tiles.sort();
+
+        let tile_to_id: HashMap<Tile, Id> = tiles
+            .into_iter()
             .enumerate()
             .map(|(id, tile)| (tile.clone(), id))
             .collect();
@@ -158,7 +305,17 @@ impl Model {
         let id_to_tile: HashMap<Id, Tile> =
             tile_to_id.iter().map(|(k, v)| (*v, k.clone())).collect();
 
-        let frequency_hints: HashMap<Id, f64> = mk_frequency_hints(&tile_to_id);
+        let id_to_frequency: HashMap<Id, i32> = tile_to_freq
+            .iter()
+            .map(|(tile, freq)| (tile_to_id[tile], *freq))
+            .collect();
+
+        let frequency_hints: HashMap<Id, f64> = mk_frequency_hints(&id_to_frequency);
+
+        let id_to_symmetry: HashMap<Id, SymmetryClass> = id_to_tile
+            .iter()
+            .map(|(id, tile)| (*id, tile.symmetry_class()))
+            .collect();
 
         let first_tile = tile_to_id.iter().next().unwrap().0;
         let tile_dimensions = (first_tile.width, first_tile.height);
@@ -167,7 +324,11 @@ impl Model {
             mode,
             id_to_tile,
             tile_to_id,
+            id_to_frequency,
             frequency_hints,
+            id_to_symmetry,
+            symmetry,
+            id_to_name: HashMap::new(),
             tile_dimensions,
             tile_to_positions,
             position_to_tile,
@@ -175,17 +336,449 @@ impl Model {
     }
 }
 
-fn mk_frequency_hints(tile_to_id: &HashMap<Tile, Id>) -> HashMap<Id, f64> {
-    let total_ids = *(&tile_to_id.keys().len()) as f64;
+// Count every `tile_dimensions` window of `image`. With `periodic_input` the image
+// is treated as tileable: windows start at every pixel and wrap around the right and
+// bottom edges. Otherwise only the windows that fit inside the image are taken, so
+// no adjacency is invented by stitching opposite edges together.
+fn overlap_tile_frequencies(
+    image: &Image,
+    tile_dimensions: (u32, u32),
+    symmetry: Symmetry,
+    periodic_input: bool,
+) -> Result<HashMap<Tile, i32>> {
+    let (tile_w, tile_h) = tile_dimensions;
+    let mut tile_to_freq: HashMap<Tile, i32> = HashMap::new();
 
-    tile_to_id
-        .iter()
-        .fold(HashMap::new(), |mut freqs: HashMap<Id, i32>, (_, id)| {
-            let next_frequency = freqs.get(id).map(|f| f + 1).unwrap_or(1);
-            freqs.insert(*id, next_frequency);
-            freqs
+    let (windows_x, windows_y) = if periodic_input {
+        (image.width, image.height)
+    } else if tile_w <= image.width && tile_h <= image.height {
+        (image.width - tile_w + 1, image.height - tile_h + 1)
+    } else {
+        return Err(anyhow!(
+            "Tile dimensions ({}, {}) don't fit in the {}x{} input. Use a smaller tile or --periodic-input.",
+            tile_w,
+            tile_h,
+            image.width,
+            image.height
+        ));
+    };
+
+    for y in 0..windows_y {
+        for x in 0..windows_x {
+            let mut pixels = vec![];
+            for y_t in y..(y + tile_h) {
+                for x_t in x..(x + tile_w) {
+                    let pixel = Vector2 {
+                        x: (x_t as i32) % image.width as i32,
+                        y: (y_t as i32) % image.height as i32,
+                    };
+                    let color = image.at(pixel);
+                    pixels.push(color);
+                }
+            }
+
+            let tile = Tile {
+                width: tile_w,
+                height: tile_h,
+                pixels,
+            };
+
+            // The occurrence counts once for every distinct variation. A symmetric
+            // tile has fewer of them, but isn't weighted any more or less than each
+            // variation of an asymmetric one.
+            let tiles = tile.variations(symmetry);
+
+            for tile in tiles {
+                let freq = tile_to_freq.get(&tile).map(|f| f + 1).unwrap_or(1);
+                tile_to_freq.insert(tile, freq);
+            }
+        }
+    }
+
+    Ok(tile_to_freq)
+}
+
+/// The tile at `position` of a sheet of `tile_dimensions` tiles.
+pub fn tile_at(image: &Image, position: Vector2, tile_dimensions: (u32, u32)) -> Tile {
+    let (tile_width, tile_height) = tile_dimensions;
+    let mut pixels = vec![];
+
+    for t_y in 0..tile_height {
+        for t_x in 0..tile_width {
+            let pixel = Vector2 {
+                x: position.x * tile_width as i32 + t_x as i32,
+                y: position.y * tile_height as i32 + t_y as i32,
+            };
+            pixels.push(image.at(pixel));
+        }
+    }
+
+    Tile {
+        width: tile_width,
+        height: tile_height,
+        pixels,
+    }
+}
+
+// Every tile of a sheet, top left to bottom right.
+fn read_tile_sheet(image_path: PathBuf, tile_dimensions: (u32, u32)) -> Result<Vec<Tile>> {
+    let image = Image::from_png(image_path)?;
+    let (tile_width, tile_height) = tile_dimensions;
+    if image.width % tile_width != 0 || image.height % tile_height != 0 {
+        return Err(anyhow!(
+            "Tile sheet must be evenly divisible by the tile dimensions."
+        ));
+    }
+
+    let (columns, rows) = (image.width / tile_width, image.height / tile_height);
+    Ok((0..rows as i32)
+        .flat_map(|y| (0..columns as i32).map(move |x| Vector2 { x, y }))
+        .map(|position| tile_at(&image, position, tile_dimensions))
+        .collect())
+}
+
+/// Optional metadata for a tile in a directory, read from the TOML file with the same
+/// name next to it. For `road.png`, `road.toml` could hold:
+///
+///   weight = 3
+///   symmetry = "rotations"
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct TileMetadata {
+    /// how many times the tile counts, as if it appeared that often
+    pub weight: i32,
+    /// overrides `--symmetry` for this tile
+    pub symmetry: Option<Symmetry>,
+}
+
+impl Default for TileMetadata {
+    fn default() -> TileMetadata {
+        TileMetadata {
+            weight: 1,
+            symmetry: None,
+        }
+    }
+}
+
+pub struct NamedTile {
+    /// the file name without its extension if the tile came from a directory, or the
+    /// name given in a rules file
+    pub name: Option<String>,
+    pub tile: Tile,
+    pub metadata: TileMetadata,
+}
+
+// Every PNG in `directory`, in file name order, along with its metadata. They must
+// all be the same size.
+fn read_tile_directory(directory: &Path) -> Result<Vec<NamedTile>> {
+    let mut paths = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+    });
+    paths.sort();
+
+    let tiles = paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+            let metadata = read_tile_metadata(&path.with_extension("toml"))?;
+            let image = Image::from_png(path)?;
+            Ok(NamedTile {
+                name,
+                tile: Tile {
+                    width: image.width,
+                    height: image.height,
+                    pixels: image.pixels,
+                },
+                metadata,
+            })
         })
+        .collect::<Result<Vec<NamedTile>>>()?;
+
+    let dimensions = |named: &NamedTile| (named.tile.width, named.tile.height);
+    match tiles.first() {
+        None => Err(anyhow!("No PNG tiles found in {}", directory.display())),
+        Some(first) if tiles.iter().any(|t| dimensions(t) != dimensions(first)) => Err(anyhow!(
+            "Every tile in {} must be the same size",
+            directory.display()
+        )),
+        Some(_) => Ok(tiles),
+    }
+}
+
+// The metadata in `path`, or the defaults if there is no such file.
+fn read_tile_metadata(path: &Path) -> Result<TileMetadata> {
+    if !path.exists() {
+        return Ok(TileMetadata::default());
+    }
+
+    let metadata: TileMetadata = toml::from_str(&fs::read_to_string(path)?)
+        .with_context(|| format!("Invalid tile metadata in {}", path.display()))?;
+    if metadata.weight < 1 {
+        return Err(anyhow!(
+            "Tile weight must be at least 1 in {}",
+            path.display()
+        ));
+    }
+    Ok(metadata)
+}
+
+fn shrink(size: u32, tile_size: u32, periodic: bool) -> u32 {
+    match periodic {
+        true => size,
+        false => size - tile_size + 1,
+    }
+}
+
+fn grow(size: u32, tile_size: u32, periodic: bool) -> u32 {
+    match periodic {
+        true => size,
+        false => size + tile_size - 1,
+    }
+}
+
+fn mk_frequency_hints(id_to_frequency: &HashMap<Id, i32>) -> HashMap<Id, f64> {
+    let total_occurrences = id_to_frequency.values().sum::<i32>() as f64;
+
+    id_to_frequency
         .iter()
-        .map(|(id, freq)| (*id, *freq as f64 / total_ids))
+        .map(|(id, freq)| (*id, *freq as f64 / total_occurrences))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::adjacency_rules::AdjacencyRules;
+    use crate::data::{
+        direction::{self, Direction},
+        mode::Mode,
+        symmetry::{Symmetry, SymmetryClass, Transform},
+        tile::Tile,
+        vector2::Vector2,
+    };
+    use crate::image::Image;
+
+    use super::{overlap_tile_frequencies, tile_at, Model};
+
+    fn mk_tile(color: u8) -> Tile {
+        Tile {
+            width: 1,
+            height: 1,
+            pixels: vec![[color, color, color]],
+        }
+    }
+
+    #[test]
+    fn test_frequency_hints_follow_occurrences() {
+        let grass = mk_tile(0);
+        let flower = mk_tile(255);
+        let model = Model::from_frequency_and_position_maps(
+            Mode::Overlap,
+            Symmetry::None,
+            HashMap::from([(grass.clone(), 9), (flower.clone(), 1)]),
+            HashMap::new(),
+            HashMap::new(),
+        );
+
+        let grass_id = model.tile_to_id[&grass];
+        let flower_id = model.tile_to_id[&flower];
+        assert_eq!(model.id_to_frequency[&grass_id], 9);
+        assert_eq!(model.id_to_frequency[&flower_id], 1);
+        assert_eq!(model.frequency_hints[&grass_id], 0.9);
+        assert_eq!(model.frequency_hints[&flower_id], 0.1);
+    }
+
+    #[test]
+    fn test_overlap_input_periodicity() {
+        // a 3x2 image whose only white pixel is in the right column
+        let mut image = Image::new(3, 2);
+        image.set_color(Vector2 { x: 2, y: 0 }, [255, 255, 255]);
+
+        let windows = overlap_tile_frequencies(&image, (2, 2), Symmetry::None, false).unwrap();
+        assert_eq!(windows.values().sum::<i32>(), 2);
+        // the white pixel never shows up on the left side of a window
+        assert!(windows.keys().all(|tile| tile.at(0, 0) != [255, 255, 255]));
+
+        let windows = overlap_tile_frequencies(&image, (2, 2), Symmetry::None, true).unwrap();
+        assert_eq!(windows.values().sum::<i32>(), 6);
+        assert!(windows.keys().any(|tile| tile.at(0, 0) == [255, 255, 255]));
+
+        assert!(overlap_tile_frequencies(&image, (3, 3), Symmetry::None, false).is_err());
+    }
+
+    #[test]
+    fn test_symmetric_variations_count_once() {
+        // a black image with a single white pixel in the corner
+        let mut image = Image::new(2, 2);
+        image.set_color(Vector2 { x: 0, y: 0 }, [255, 255, 255]);
+
+        // every window is the same: the 2x2 corner tile, symmetric along a diagonal
+        let windows = overlap_tile_frequencies(&image, (2, 2), Symmetry::All, false).unwrap();
+        assert_eq!(windows.len(), 4);
+        assert!(windows.values().all(|freq| *freq == 1));
+
+        let solid =
+            overlap_tile_frequencies(&Image::new(2, 2), (1, 1), Symmetry::All, false).unwrap();
+        assert_eq!(solid.values().collect::<Vec<_>>(), vec![&4]);
+
+        let model = Model::from_frequency_and_position_maps(
+            Mode::Overlap,
+            Symmetry::All,
+            windows,
+            HashMap::new(),
+            HashMap::new(),
+        );
+        assert!(model
+            .id_to_symmetry
+            .values()
+            .all(|symmetry| *symmetry == SymmetryClass::L));
+    }
+
+    #[test]
+    fn test_wave_dimensions() {
+        let mut model = Model::from_frequency_and_position_maps(
+            Mode::Overlap,
+            Symmetry::None,
+            HashMap::from([(mk_tile(0), 1)]),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        model.tile_dimensions = (3, 2);
+
+        for periodic_output in [(false, false), (true, false), (true, true)] {
+            let wave_dimensions = model.wave_dimensions((10, 8), periodic_output).unwrap();
+            assert_eq!(
+                model.output_dimensions(wave_dimensions, periodic_output),
+                (10, 8)
+            );
+        }
+        assert_eq!(
+            model.wave_dimensions((10, 8), (false, true)).unwrap(),
+            (8, 8)
+        );
+        assert!(model.wave_dimensions((2, 8), (true, true)).is_err());
+    }
+
+    #[test]
+    fn test_tiled_variations_keep_their_neighbors() {
+        // two 2x2 tiles side by side: 'a' with a white top left corner, and 'b' to its
+        // right with a white top edge
+        let mut image = Image::new(4, 2);
+        for x in [0, 2, 3] {
+            image.set_color(Vector2 { x, y: 0 }, [255, 255, 255]);
+        }
+
+        let model = Model::tiled_from_image(&image, (2, 2), Symmetry::Rotations).unwrap();
+        assert_eq!(model.id_to_tile.len(), 8);
+
+        let rules = AdjacencyRules::from_tile_model(&model);
+        let a = tile_at(&image, Vector2 { x: 0, y: 0 }, (2, 2));
+        let b = tile_at(&image, Vector2 { x: 1, y: 0 }, (2, 2));
+        for (transform, direction) in [
+            (Transform::Identity, Direction::RIGHT),
+            (Transform::Rotate90, Direction::DOWN),
+            (Transform::Rotate180, Direction::LEFT),
+            (Transform::Rotate270, Direction::UP),
+        ] {
+            let a = model.tile_to_id[&a.transform(transform)];
+            let b = model.tile_to_id[&b.transform(transform)];
+            assert!(rules.rules[a][b][direction.idx()], "{}", transform);
+            // and nowhere else
+            for other in direction::ALL.into_iter().filter(|d| *d != direction) {
+                assert!(!rules.rules[a][b][other.idx()], "{}", transform);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tile_set_from_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = temp_dir.path().to_path_buf();
+
+        // a black tile, and a tile with a white left column
+        let black = Image::new(2, 2);
+        let mut left_edge = Image::new(2, 2);
+        left_edge.set_color(Vector2 { x: 0, y: 0 }, [255, 255, 255]);
+        left_edge.set_color(Vector2 { x: 0, y: 1 }, [255, 255, 255]);
+        black.save(directory.join("a").to_str().unwrap()).unwrap();
+        left_edge
+            .save(directory.join("b").to_str().unwrap())
+            .unwrap();
+        std::fs::write(directory.join("notes.txt"), "not a tile").unwrap();
+
+        let model = Model::tile_set(directory.clone(), None, Symmetry::None).unwrap();
+
+        assert_eq!(model.id_to_tile.len(), 2);
+        assert_eq!(model.tile_dimensions, (2, 2));
+
+        let rules = AdjacencyRules::from_tile_set_model(&model);
+        let id = |image: &Image| {
+            model.tile_to_id[&Tile {
+                width: 2,
+                height: 2,
+                pixels: image.pixels.clone(),
+            }]
+        };
+        let (black, left_edge) = (id(&black), id(&left_edge));
+        // the white column can't touch a black one
+        assert!(rules.rules[black][left_edge][Direction::LEFT.idx()]);
+        assert!(!rules.rules[black][left_edge][Direction::RIGHT.idx()]);
+        assert!(!rules.rules[left_edge][left_edge][Direction::LEFT.idx()]);
+        assert!(rules.rules[left_edge][left_edge][Direction::UP.idx()]);
+    }
+
+    #[test]
+    fn test_tiled_directory_with_names_and_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = temp_dir.path().to_path_buf();
+
+        // a plain tile, and a corner that is varied by rotation and counts three times
+        let grass = Image::new(2, 2);
+        let mut corner = Image::new(2, 2);
+        corner.set_color(Vector2 { x: 0, y: 0 }, [255, 255, 255]);
+        grass
+            .save(directory.join("grass").to_str().unwrap())
+            .unwrap();
+        corner
+            .save(directory.join("corner").to_str().unwrap())
+            .unwrap();
+        std::fs::write(
+            directory.join("corner.toml"),
+            "weight = 3\nsymmetry = \"rotations\"\n",
+        )
+        .unwrap();
+
+        let model = Model::tiled(directory.clone(), (2, 2), Symmetry::None);
+        let wrong_dimensions = Model::tiled(directory.clone(), (3, 3), Symmetry::None);
+        std::fs::write(directory.join("corner.toml"), "weight = 0\n").unwrap();
+        let zero_weight = Model::tiled(directory.clone(), (2, 2), Symmetry::None);
+
+        let model = model.unwrap();
+        assert!(wrong_dimensions.is_err());
+        assert!(zero_weight.is_err());
+
+        let mut names = model.id_to_name.values().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "corner",
+                "corner rotate-180",
+                "corner rotate-270",
+                "corner rotate-90",
+                "grass"
+            ]
+        );
+        for (id, name) in &model.id_to_name {
+            let expected = if name == "grass" { 1 } else { 3 };
+            assert_eq!(model.id_to_frequency[id], expected);
+        }
+    }
+}
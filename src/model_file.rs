@@ -0,0 +1,220 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    adjacency_rules::AdjacencyRules,
+    data::{color::RGB, id::Id, mode::Mode, symmetry::Symmetry, tile::Tile, vector2::Vector2},
+    model::Model,
+};
+
+// Bumped whenever the layout of `ModelFile` changes, so that files written by another
+// version are rejected instead of misread.
+const FORMAT_VERSION: u32 = 1;
+
+// A learned `Model` and its `AdjacencyRules`, as written by `learn` and read back by
+// `generate --model`. Only what generating needs is kept: where the tiles were found
+// in the input is dropped, and the frequency hints and symmetry classes are derived
+// again on load.
+#[derive(Serialize, Deserialize)]
+struct ModelFile {
+    version: u32,
+    mode: Mode,
+    symmetry: Symmetry,
+    tile_dimensions: (u32, u32),
+    // in id order
+    tiles: Vec<SavedTile>,
+    offsets: Vec<(i32, i32)>,
+    // `compatible[a][k]`: the tiles that may be placed at `offsets[k]` from 'a'. Most
+    // pairs of tiles aren't compatible, so this is much smaller than the full
+    // `AdjacencyRules::rules`.
+    compatible: Vec<Vec<Vec<Id>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTile {
+    pixels: Vec<RGB>,
+    frequency: i32,
+    name: Option<String>,
+}
+
+// Just enough of a `ModelFile` to tell which version wrote it.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+pub fn save_model(path: &Path, model: &Model, adjacency_rules: &AdjacencyRules) -> Result<()> {
+    let num_tiles = model.id_to_tile.len();
+    let tiles = (0..num_tiles)
+        .map(|id| SavedTile {
+            pixels: model.id_to_tile[&id].pixels.clone(),
+            frequency: model.id_to_frequency[&id],
+            name: model.id_to_name.get(&id).cloned(),
+        })
+        .collect();
+    let compatible = adjacency_rules
+        .rules
+        .iter()
+        .map(|rules| {
+            (0..adjacency_rules.offsets.len())
+                .map(|k| (0..num_tiles).filter(|b| rules[*b][k]).collect())
+                .collect()
+        })
+        .collect();
+
+    let model_file = ModelFile {
+        version: FORMAT_VERSION,
+        mode: model.mode,
+        symmetry: model.symmetry,
+        tile_dimensions: model.tile_dimensions,
+        tiles,
+        offsets: adjacency_rules
+            .offsets
+            .iter()
+            .map(|offset| (offset.x, offset.y))
+            .collect(),
+        compatible,
+    };
+    fs::write(path, serde_json::to_string(&model_file)?)
+        .with_context(|| format!("Could not write model to {}", path.display()))
+}
+
+pub fn load_model(path: &Path) -> Result<(Model, AdjacencyRules)> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Could not read model from {}", path.display()))?;
+    let Version { version } = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a saved model", path.display()))?;
+    if version != FORMAT_VERSION {
+        return Err(anyhow!(
+            "{} was saved in model format {}, but only format {} can be read. Run `learn` again.",
+            path.display(),
+            version,
+            FORMAT_VERSION
+        ));
+    }
+    let model_file: ModelFile = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid model in {}", path.display()))?;
+
+    let (width, height) = model_file.tile_dimensions;
+    let tiles = model_file
+        .tiles
+        .iter()
+        .map(|saved| Tile {
+            width,
+            height,
+            pixels: saved.pixels.clone(),
+        })
+        .collect::<Vec<Tile>>();
+    if tiles.is_empty()
+        || tiles
+            .iter()
+            .any(|tile| tile.pixels.len() != (width * height) as usize)
+    {
+        return Err(anyhow!("Invalid tiles in {}", path.display()));
+    }
+
+    let tile_to_freq = tiles
+        .iter()
+        .zip(&model_file.tiles)
+        .map(|(tile, saved)| (tile.clone(), saved.frequency))
+        .collect();
+    let tile_to_name = tiles
+        .iter()
+        .zip(&model_file.tiles)
+        .filter_map(|(tile, saved)| saved.name.clone().map(|name| (tile.clone(), name)))
+        .collect();
+    let model = Model::from_learned_tiles(
+        model_file.mode,
+        model_file.symmetry,
+        tile_to_freq,
+        tile_to_name,
+    );
+
+    // the ids of the saved tiles in the new model
+    let ids = tiles
+        .iter()
+        .map(|tile| model.tile_to_id[tile])
+        .collect::<Vec<Id>>();
+    let offsets = model_file
+        .offsets
+        .iter()
+        .map(|(x, y)| Vector2 { x: *x, y: *y })
+        .collect::<Vec<Vector2>>();
+    let mut adjacency_rules =
+        AdjacencyRules::with_offsets(tiles.len(), offsets.iter().skip(4).copied().collect());
+    // a tile saved twice would have been merged into one
+    if model.id_to_tile.len() != tiles.len()
+        || adjacency_rules.offsets != offsets
+        || model_file.compatible.len() != tiles.len()
+    {
+        return Err(anyhow!("Invalid adjacency rules in {}", path.display()));
+    }
+
+    for (a, compatible) in model_file.compatible.iter().enumerate() {
+        for (k, tiles) in compatible.iter().enumerate().take(offsets.len()) {
+            for b in tiles {
+                let b = *ids
+                    .get(*b)
+                    .ok_or_else(|| anyhow!("Invalid adjacency rules in {}", path.display()))?;
+                adjacency_rules.rules[ids[a]][b][k] = true;
+            }
+        }
+    }
+
+    Ok((model, adjacency_rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::adjacency_rules::AdjacencyRules;
+    use crate::data::{mode::Mode, symmetry::Symmetry, tile::Tile};
+    use crate::model::Model;
+
+    use super::{load_model, save_model};
+
+    fn mk_tile(color: u8) -> Tile {
+        Tile {
+            width: 2,
+            height: 2,
+            pixels: vec![[color, color, color]; 4],
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_model() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("model.json");
+        let model = Model::from_learned_tiles(
+            Mode::Overlap,
+            Symmetry::Rotations,
+            HashMap::from([(mk_tile(0), 3), (mk_tile(128), 1), (mk_tile(255), 2)]),
+            HashMap::from([(mk_tile(255), "white".to_string())]),
+        );
+        let adjacency_rules = AdjacencyRules::from_overlap_model(&model);
+
+        save_model(&path, &model, &adjacency_rules).unwrap();
+        let loaded = load_model(&path);
+        std::fs::write(&path, r#"{ "version": 0 }"#).unwrap();
+        let outdated = load_model(&path);
+        let (loaded_model, loaded_rules) = loaded.unwrap();
+
+        assert_eq!(loaded_model.mode, Mode::Overlap);
+        assert_eq!(loaded_model.symmetry, Symmetry::Rotations);
+        assert_eq!(loaded_model.tile_dimensions, (2, 2));
+        assert_eq!(loaded_model.id_to_tile, model.id_to_tile);
+        assert_eq!(loaded_model.id_to_frequency, model.id_to_frequency);
+        assert_eq!(loaded_model.frequency_hints, model.frequency_hints);
+        assert_eq!(loaded_model.id_to_name, model.id_to_name);
+        assert_eq!(loaded_rules.offsets, adjacency_rules.offsets);
+        assert_eq!(loaded_rules.rules, adjacency_rules.rules);
+
+        match outdated {
+            Err(error) => assert!(error.to_string().contains("format 0")),
+            Ok(_) => panic!("a model of another format was read"),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use crate::data::{id::Id, vector2::Vector2};
+use crate::wave_function::{Solution, WaveFunction, WaveFunctionError};
+
+/// Gets told what a `WaveFunction` does while `WaveFunction::run_observed` collapses
+/// it. Each call comes after the step it is about, so the wave already shows its
+/// outcome. Every method does nothing by default.
+pub trait Observer {
+    /// `tile` was picked for the cell at `coord`.
+    fn on_collapse(&mut self, _wave_function: &WaveFunction, _coord: Vector2, _tile: Id) {}
+
+    /// A step was propagated, banning `bans` choices across the wave.
+    fn on_propagate(&mut self, _wave_function: &WaveFunction, _bans: usize) {}
+
+    /// `collapsed` out of `total` cells are collapsed.
+    fn on_progress(&mut self, _collapsed: u32, _total: u32) {}
+
+    /// The cell at `coord` ran out of choices. The wave is backtracked or started over
+    /// next, see `on_restart`.
+    fn on_contradiction(&mut self, _wave_function: &WaveFunction, _coord: Vector2) {}
+
+    /// Attempt number `attempt` ended in a contradiction at `coord` that backtracking
+    /// couldn't get out of, so the wave was started over.
+    fn on_restart(&mut self, _wave_function: &WaveFunction, _attempt: usize, _coord: Vector2) {}
+
+    /// Every cell is collapsed.
+    fn on_complete(&mut self, _wave_function: &WaveFunction, _solution: &Solution) {}
+}
+
+/// Prints how far along the wave is every ten percent, every failed attempt, and the
+/// attempts and iterations it took once it's done.
+pub struct ConsoleProgress;
+
+impl Observer for ConsoleProgress {
+    fn on_progress(&mut self, collapsed: u32, total: u32) {
+        let ten_percent = (total / 10).max(1);
+        if collapsed.is_multiple_of(ten_percent) {
+            println!("Progress: {}%", collapsed * 100 / total)
+        }
+    }
+
+    fn on_restart(&mut self, _wave_function: &WaveFunction, attempt: usize, coord: Vector2) {
+        println!(
+            "Attempt {} failed: {}. Restarting.",
+            attempt,
+            WaveFunctionError::Contradiction { coord }
+        );
+    }
+
+    fn on_complete(&mut self, _wave_function: &WaveFunction, solution: &Solution) {
+        println!("Attempts: {}", solution.attempts);
+        println!("Iterations completed: {}", solution.iterations);
+    }
+}
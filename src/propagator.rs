@@ -0,0 +1,96 @@
+use crate::adjacency_rules::AdjacencyRules;
+use crate::data::{id::Id, vector2::Vector2};
+
+// `AdjacencyRules` flattened into lists, so that propagation only visits the tiles
+// that are actually compatible instead of checking every pair (the AC-4 algorithm).
+pub struct Propagator {
+    // the offsets of `AdjacencyRules`, each one a cell constrained by the tiles of
+    // the cell it's relative to
+    offsets: Vec<Vector2>,
+    // `compatible[tile][k]` lists the tiles that may be placed at `offsets[k]` from
+    // `tile`
+    compatible: Vec<Vec<Vec<Id>>>,
+}
+
+impl Propagator {
+    pub fn new(adjacency_rules: &AdjacencyRules) -> Propagator {
+        let num_tiles = adjacency_rules.rules.len();
+        assert!(
+            num_tiles <= u16::MAX as usize,
+            "Support counters can't track more than {} tiles",
+            u16::MAX
+        );
+
+        let compatible = adjacency_rules
+            .rules
+            .iter()
+            .map(|rules| {
+                (0..adjacency_rules.offsets.len())
+                    .map(|k| (0..num_tiles).filter(|b| rules[*b][k]).collect())
+                    .collect()
+            })
+            .collect();
+
+        Propagator {
+            offsets: adjacency_rules.offsets.clone(),
+            compatible,
+        }
+    }
+
+    pub fn offsets(&self) -> &[Vector2] {
+        &self.offsets
+    }
+
+    pub fn compatible(&self, tile: Id, k: usize) -> &[Id] {
+        &self.compatible[tile][k]
+    }
+
+    // Where the counter of `tile` at `offsets[k]` lives in `CellState::support`.
+    pub fn support_idx(&self, tile: Id, k: usize) -> usize {
+        tile * self.offsets.len() + k
+    }
+
+    // The support counters of a cell with every tile still possible around it. The
+    // counter of `tile` at `offsets[k]` (see `support_idx`) is how many tiles of the
+    // cell at `-offsets[k]` allow `tile` to be placed at `offsets[k]` from them.
+    pub fn initial_support(&self) -> Vec<u16> {
+        let mut support = vec![0; self.compatible.len() * self.offsets.len()];
+        for compatible in &self.compatible {
+            for (k, tiles) in compatible.iter().enumerate() {
+                for tile in tiles {
+                    support[self.support_idx(*tile, k)] += 1;
+                }
+            }
+        }
+        support
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adjacency_rules::AdjacencyRules;
+    use crate::data::direction::Direction;
+
+    use super::Propagator;
+
+    #[test]
+    fn test_compatible_and_support() {
+        // 0 may sit above 1, and 1 below 0. Both may sit next to themselves sideways.
+        let mut rules = AdjacencyRules::new(2);
+        rules.allow(0, 1, Direction::DOWN);
+        rules.allow(1, 0, Direction::UP);
+        for tile in 0..2 {
+            rules.allow(tile, tile, Direction::LEFT);
+            rules.allow(tile, tile, Direction::RIGHT);
+        }
+        let propagator = Propagator::new(&rules);
+
+        assert_eq!(propagator.compatible(0, Direction::DOWN.idx()), &[1]);
+        assert!(propagator.compatible(0, Direction::UP.idx()).is_empty());
+        assert_eq!(propagator.compatible(1, Direction::RIGHT.idx()), &[1]);
+
+        let support = propagator.initial_support();
+        assert_eq!(support[propagator.support_idx(1, 0)..][..4], [0, 1, 1, 1]);
+        assert_eq!(support[propagator.support_idx(0, 0)..][..4], [1, 0, 1, 1]);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    adjacency_rules::AdjacencyRules,
+    data::{
+        direction::Direction,
+        symmetry::{Symmetry, Transform},
+        vector2::Vector2,
+    },
+    image::Image,
+    model::{tile_at, Model, NamedTile, TileMetadata},
+};
+
+// A hand-authored tile set, in the spirit of the XML tile sets of the original WFC.
+// Instead of inferring which tiles may touch from pixels, the document lists the
+// allowed pairs. In TOML, with paths relative to the document:
+//
+//   tile_dimensions = [16, 16] # only needed for tiles cut from a sheet
+//
+//   [[tiles]]
+//   name = "grass"
+//   image = "grass.png"
+//   weight = 4
+//
+//   [[tiles]]
+//   name = "road"
+//   sheet = "roads.png"
+//   position = [1, 0] # in tiles, not pixels
+//   symmetry = "rotations"
+//
+//   [neighbors]
+//   right = [["grass", "grass"], ["grass", "road"], ["road", "road"]]
+//   down = [["grass", "grass"]]
+//
+// `["grass", "road"]` under `right` means a road may be placed to the right of
+// grass, which also lets grass be placed to the left of a road. A `.json` document
+// has the same fields.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    tile_dimensions: Option<(u32, u32)>,
+    tiles: Vec<TileEntry>,
+    #[serde(default)]
+    neighbors: Neighbors,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TileEntry {
+    name: String,
+    // a PNG holding just this tile
+    image: Option<PathBuf>,
+    // or a sheet to cut the tile at `position` from
+    sheet: Option<PathBuf>,
+    position: Option<(u32, u32)>,
+    #[serde(default = "default_weight")]
+    weight: i32,
+    // overrides `--symmetry` for this tile
+    symmetry: Option<Symmetry>,
+}
+
+fn default_weight() -> i32 {
+    1
+}
+
+// Pairs of tile names, the second of which may be placed in that direction from the
+// first.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+struct Neighbors {
+    up: Vec<(String, String)>,
+    down: Vec<(String, String)>,
+    left: Vec<(String, String)>,
+    right: Vec<(String, String)>,
+}
+
+/// The model and rules described by the document at `path`. Tiles are varied by
+/// `symmetry` unless they set their own. A variation is named after its tile and the
+/// transform, like "road rotate-90", so it can be listed in `neighbors` as well.
+/// Every pair is also allowed for each variation of it, transformed along with the
+/// direction: if a road may be right of grass, a quarter turn of that road may be
+/// below grass.
+pub fn read_rules_file(path: &Path, symmetry: Symmetry) -> Result<(Model, AdjacencyRules)> {
+    let contents = fs::read_to_string(path)?;
+    let rules_file: RulesFile = match path.extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("json") => {
+            serde_json::from_str(&contents).map_err(anyhow::Error::from)
+        }
+        _ => toml::from_str(&contents).map_err(anyhow::Error::from),
+    }
+    .with_context(|| format!("Invalid rules file {}", path.display()))?;
+
+    let base_directory = path.parent().unwrap_or(Path::new(""));
+    let tiles = read_tiles(&rules_file, base_directory)?;
+    let names = tiles
+        .iter()
+        .filter_map(|named| named.name.clone())
+        .collect::<Vec<String>>();
+
+    let model = Model::from_named_tiles(tiles, symmetry);
+    let name_to_id = model
+        .id_to_name
+        .iter()
+        .map(|(id, name)| (name.as_str(), *id))
+        .collect::<HashMap<&str, usize>>();
+    // identical tiles are merged under the first name, so the others would be lost
+    if let Some(name) = names
+        .iter()
+        .find(|name| !name_to_id.contains_key(name.as_str()))
+    {
+        return Err(anyhow!(
+            "Tile '{}' looks exactly like another tile in {}",
+            name,
+            path.display()
+        ));
+    }
+
+    let mut rules = AdjacencyRules::new(model.id_to_tile.len());
+    let neighbors = [
+        (Direction::UP, &rules_file.neighbors.up),
+        (Direction::DOWN, &rules_file.neighbors.down),
+        (Direction::LEFT, &rules_file.neighbors.left),
+        (Direction::RIGHT, &rules_file.neighbors.right),
+    ];
+    for (direction, pairs) in neighbors {
+        for (a, b) in pairs {
+            let id = |name: &String| {
+                name_to_id
+                    .get(name.as_str())
+                    .copied()
+                    .ok_or_else(|| anyhow!("Unknown tile '{}' in {}", name, path.display()))
+            };
+            let (a, b) = (&model.id_to_tile[&id(a)?], &model.id_to_tile[&id(b)?]);
+
+            for transform in Transform::ALL {
+                let (Some(a), Some(b)) = (
+                    model.tile_to_id.get(&a.transform(transform)),
+                    model.tile_to_id.get(&b.transform(transform)),
+                ) else {
+                    continue;
+                };
+                let direction = transform.apply(direction);
+                rules.allow(*a, *b, direction);
+                rules.allow(*b, *a, Transform::Rotate180.apply(direction));
+            }
+        }
+    }
+
+    Ok((model, rules))
+}
+
+fn read_tiles(rules_file: &RulesFile, base_directory: &Path) -> Result<Vec<NamedTile>> {
+    let mut sheets: HashMap<&PathBuf, Image> = HashMap::new();
+    let mut tiles = vec![];
+
+    for entry in &rules_file.tiles {
+        if entry.weight < 1 {
+            return Err(anyhow!(
+                "Tile weight must be at least 1 for '{}'",
+                entry.name
+            ));
+        }
+
+        let tile = match (&entry.image, &entry.sheet, entry.position) {
+            (Some(image), None, None) => {
+                let image = Image::from_png(base_directory.join(image))?;
+                tile_at(&image, Vector2 { x: 0, y: 0 }, (image.width, image.height))
+            }
+            (None, Some(sheet), Some((x, y))) => {
+                let tile_dimensions = rules_file.tile_dimensions.ok_or_else(|| {
+                    anyhow!(
+                        "Tile dimensions are required to cut '{}' from a sheet",
+                        entry.name
+                    )
+                })?;
+                if !sheets.contains_key(sheet) {
+                    sheets.insert(sheet, Image::from_png(base_directory.join(sheet))?);
+                }
+                let image = &sheets[sheet];
+                if (x + 1) * tile_dimensions.0 > image.width
+                    || (y + 1) * tile_dimensions.1 > image.height
+                {
+                    return Err(anyhow!(
+                        "Tile '{}' at ({}, {}) is outside of {}",
+                        entry.name,
+                        x,
+                        y,
+                        sheet.display()
+                    ));
+                }
+                let position = Vector2 {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                tile_at(image, position, tile_dimensions)
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Tile '{}' needs either an `image`, or a `sheet` and a `position`",
+                    entry.name
+                ))
+            }
+        };
+
+        tiles.push(NamedTile {
+            name: Some(entry.name.clone()),
+            tile,
+            metadata: TileMetadata {
+                weight: entry.weight,
+                symmetry: entry.symmetry,
+            },
+        });
+    }
+
+    let first = tiles
+        .first()
+        .ok_or_else(|| anyhow!("A rules file needs at least one tile"))?;
+    let dimensions = (first.tile.width, first.tile.height);
+    if let Some(other) = tiles
+        .iter()
+        .find(|named| (named.tile.width, named.tile.height) != dimensions)
+    {
+        return Err(anyhow!(
+            "Every tile must be the same size, but '{}' is {}x{} rather than {}x{}",
+            other.name.as_deref().unwrap_or_default(),
+            other.tile.width,
+            other.tile.height,
+            dimensions.0,
+            dimensions.1
+        ));
+    }
+
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::data::{direction::Direction, symmetry::Symmetry, vector2::Vector2};
+    use crate::image::Image;
+
+    use super::read_rules_file;
+
+    // A black grass tile, and a sheet whose second tile is a road with one white
+    // corner.
+    // The directory is removed once the returned `TempDir` is dropped.
+    fn mk_directory() -> TempDir {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = temp_dir.path();
+
+        Image::new(2, 2)
+            .save(directory.join("grass").to_str().unwrap())
+            .unwrap();
+        let mut sheet = Image::new(4, 2);
+        sheet.set_color(Vector2 { x: 2, y: 0 }, [255, 255, 255]);
+        sheet
+            .save(directory.join("sheet").to_str().unwrap())
+            .unwrap();
+
+        temp_dir
+    }
+
+    #[test]
+    fn test_rules_from_toml() {
+        let directory = mk_directory();
+        let path = directory.path().join("rules.toml");
+        std::fs::write(
+            &path,
+            r#"
+            tile_dimensions = [2, 2]
+
+            [[tiles]]
+            name = "grass"
+            image = "grass.png"
+            weight = 4
+
+            [[tiles]]
+            name = "road"
+            sheet = "sheet.png"
+            position = [1, 0]
+            symmetry = "rotations"
+
+            [neighbors]
+            right = [["grass", "road"]]
+            "#,
+        )
+        .unwrap();
+
+        let result = read_rules_file(&path, Symmetry::None);
+        let (model, rules) = result.unwrap();
+
+        let id = |name: &str| {
+            *model
+                .id_to_name
+                .iter()
+                .find(|(_, tile_name)| tile_name.as_str() == name)
+                .unwrap()
+                .0
+        };
+        let (grass, road, turned_road) = (id("grass"), id("road"), id("road rotate-90"));
+        assert_eq!(model.id_to_tile.len(), 5);
+        assert_eq!(model.id_to_frequency[&grass], 4);
+
+        assert!(rules.rules[grass][road][Direction::RIGHT.idx()]);
+        assert!(rules.rules[road][grass][Direction::LEFT.idx()]);
+        // the pair is turned along with the road
+        assert!(rules.rules[grass][turned_road][Direction::DOWN.idx()]);
+        assert!(rules.rules[turned_road][grass][Direction::UP.idx()]);
+        // nothing else was allowed
+        assert!(!rules.rules[grass][road][Direction::LEFT.idx()]);
+        assert!(!rules.rules[grass][grass][Direction::RIGHT.idx()]);
+        assert!(!rules.rules[road][road][Direction::RIGHT.idx()]);
+    }
+
+    #[test]
+    fn test_rules_from_json() {
+        let directory = mk_directory();
+        let path = directory.path().join("rules.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "tiles": [{ "name": "grass", "image": "grass.png" }],
+                "neighbors": { "down": [["grass", "grass"]] }
+            }"#,
+        )
+        .unwrap();
+        let unknown_path = directory.path().join("unknown.json");
+        std::fs::write(
+            &unknown_path,
+            r#"{
+                "tiles": [{ "name": "grass", "image": "grass.png" }],
+                "neighbors": { "down": [["grass", "water"]] }
+            }"#,
+        )
+        .unwrap();
+
+        let result = read_rules_file(&path, Symmetry::All);
+        let unknown = read_rules_file(&unknown_path, Symmetry::None);
+        let (model, rules) = result.unwrap();
+
+        assert_eq!(model.id_to_tile.len(), 1);
+        // grass looks the same every way around, so it may be next to itself anywhere
+        for direction in [
+            Direction::UP,
+            Direction::DOWN,
+            Direction::LEFT,
+            Direction::RIGHT,
+        ] {
+            assert!(rules.rules[0][0][direction.idx()]);
+        }
+        assert!(unknown.is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Result};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng, RngCore};
+
+use crate::data::id::Id;
+
+/// Decides which tile a cell collapses into.
+pub trait TileSelector {
+    /// One of `choices`, the tiles still possible in the cell. `weights[i]` is the
+    /// share of the input that `choices[i]` makes up. Any randomness must come from
+    /// `rng`, so that a seed reproduces a run.
+    fn select(&self, choices: &[Id], weights: &[f64], rng: &mut dyn RngCore) -> Result<Id>;
+}
+
+/// As often as each tile occurs in the input.
+pub struct WeightedRandom;
+
+impl TileSelector for WeightedRandom {
+    fn select(&self, choices: &[Id], weights: &[f64], rng: &mut dyn RngCore) -> Result<Id> {
+        let dist = WeightedIndex::new(weights)?;
+        Ok(choices[dist.sample(rng)])
+    }
+}
+
+/// Every tile equally often, however rare it is in the input.
+pub struct UniformRandom;
+
+impl TileSelector for UniformRandom {
+    fn select(&self, choices: &[Id], _weights: &[f64], rng: &mut dyn RngCore) -> Result<Id> {
+        match choices {
+            [] => Err(anyhow!("No tiles to choose from")),
+            _ => Ok(choices[rng.gen_range(0..choices.len())]),
+        }
+    }
+}
+
+/// Always the most common tile, picking randomly between equally common ones.
+pub struct MostFrequent;
+
+impl TileSelector for MostFrequent {
+    fn select(&self, choices: &[Id], weights: &[f64], rng: &mut dyn RngCore) -> Result<Id> {
+        let most = weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let most_frequent = choices
+            .iter()
+            .zip(weights)
+            .filter(|(_, weight)| **weight == most)
+            .map(|(id, _)| *id)
+            .collect::<Vec<Id>>();
+        UniformRandom.select(&most_frequent, &[], rng)
+    }
+}
+
+/// Like `WeightedRandom`, with every weight raised to the power of `1 / temperature`
+/// first. Below 1 common tiles get picked even more often than in the input, down to
+/// `MostFrequent` as it nears 0. Above 1 the odds flatten out towards
+/// `UniformRandom`.
+pub struct Temperature {
+    pub temperature: f64,
+}
+
+impl TileSelector for Temperature {
+    fn select(&self, choices: &[Id], weights: &[f64], rng: &mut dyn RngCore) -> Result<Id> {
+        if self.temperature <= 0.0 {
+            return Err(anyhow!(
+                "Temperature must be above 0, not {}",
+                self.temperature
+            ));
+        }
+
+        // scaled in log space, relative to the largest weight, so that a low
+        // temperature can't overflow
+        let scaled = weights
+            .iter()
+            .map(|weight| weight.ln() / self.temperature)
+            .collect::<Vec<f64>>();
+        let max = scaled.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let weights = scaled
+            .iter()
+            .map(|scaled| (scaled - max).exp())
+            .collect::<Vec<f64>>();
+        WeightedRandom.select(choices, &weights, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{MostFrequent, Temperature, TileSelector, UniformRandom, WeightedRandom};
+
+    // How many times each of three tiles, weighted 0.7, 0.2 and 0.1, is picked out of
+    // 10000.
+    fn counts(selector: &dyn TileSelector) -> [usize; 3] {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = [0; 3];
+        for _ in 0..10000 {
+            let id = selector
+                .select(&[0, 1, 2], &[0.7, 0.2, 0.1], &mut rng)
+                .unwrap();
+            counts[id] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_selectors_follow_their_distribution() {
+        let [common, _, rare] = counts(&WeightedRandom);
+        assert!((6500..7500).contains(&common));
+        assert!((700..1300).contains(&rare));
+
+        let [common, _, rare] = counts(&UniformRandom);
+        assert!((3000..3700).contains(&common));
+        assert!((3000..3700).contains(&rare));
+
+        assert_eq!(counts(&MostFrequent), [10000, 0, 0]);
+    }
+
+    #[test]
+    fn test_temperature_sharpens_and_flattens() {
+        // 1 changes nothing
+        let [common, _, _] = counts(&Temperature { temperature: 1.0 });
+        assert!((6500..7500).contains(&common));
+
+        // 0.5 squares the weights: 0.49, 0.04 and 0.01 out of 0.54
+        let [common, _, rare] = counts(&Temperature { temperature: 0.5 });
+        assert!((8700..9400).contains(&common));
+        assert!(rare < 400);
+
+        // far below 1 it always picks the most common tile, far above 1 any tile
+        assert_eq!(counts(&Temperature { temperature: 0.001 }), [10000, 0, 0]);
+        let [common, _, rare] = counts(&Temperature {
+            temperature: 1000.0,
+        });
+        assert!((3000..3700).contains(&common));
+        assert!((3000..3700).contains(&rare));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(Temperature { temperature: 0.0 }
+            .select(&[0], &[1.0], &mut rng)
+            .is_err());
+    }
+}
@@ -1,31 +1,146 @@
 use anyhow::Result;
-use rand::{self, distributions::WeightedIndex, prelude::Distribution};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashSet, VecDeque};
+use std::{fmt, mem};
 
+use crate::cell_selector::{CellSelector, Entropy};
 use crate::data::color::Color;
 use crate::data::mode::Mode;
-use crate::data::{cell_state::CellState, id::Id, vector2::Vector2};
-use crate::gif_builder::GifBuilder;
-use crate::unique_stack::UniqueStack;
-use crate::{adjacency_rules::AdjacencyRules, helpers, image::Image, model::Model};
+use crate::data::{bitset::BitSet, cell_state::CellState, grid::Grid, id::Id, vector2::Vector2};
+use crate::entropy_heap::EntropyHeap;
+use crate::observer::Observer;
+use crate::propagator::Propagator;
+use crate::tile_selector::{TileSelector, WeightedRandom};
+use crate::{adjacency_rules::AdjacencyRules, image::Image, model::Model};
 
-// Rendering a snapshot everytime a cell collapses is visually uninteresting (no one
-// likes to watch a 40 second gif). To save space and time, we only cache a state if
-// `SNAPSHOT_COUNTER % GIF_SIZE_FACTOR == 0`. See: `WaveFunction::should_take_snapshot`.
-static SNAPSHOT_COUNTER: AtomicUsize = AtomicUsize::new(0);
-const GIF_SIZE_FACTOR: usize = 10;
+// Upper bound of the random noise added to each cell's entropy. Small enough to never
+// outweigh a real difference in entropy, but enough to break ties.
+const ENTROPY_NOISE: f64 = 1e-6;
+
+/// Why collapsing the wave failed.
+#[derive(Debug)]
+pub enum WaveFunctionError {
+    /// Propagation removed every choice from the cell at `coord`.
+    Contradiction { coord: Vector2 },
+    /// Every attempt ended in a contradiction.
+    Unsatisfiable { attempts: usize },
+}
+
+impl fmt::Display for WaveFunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaveFunctionError::Contradiction { coord } => write!(
+                f,
+                "Contradiction: no choices left for cell ({}, {})",
+                coord.x, coord.y
+            ),
+            WaveFunctionError::Unsatisfiable { attempts } => {
+                write!(f, "Unsatisfiable after {} attempts", attempts)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WaveFunctionError {}
+
+/// What happened to the wave during a `WaveFunction::step`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// `tile` was picked for the cell at `coord`.
+    Collapsed { coord: Vector2, tile: Id },
+    /// `tile` can no longer be picked for the cell at `coord`.
+    Banned { coord: Vector2, tile: Id },
+    /// The cell at `coord` ran out of choices. The wave is backtracked or started
+    /// over, so the cells may no longer hold what the events before this add up to.
+    Contradiction { coord: Vector2 },
+    /// Attempt number `attempt` ended in a contradiction at `coord` that backtracking
+    /// couldn't get out of, so the wave was started over.
+    Restarted { attempt: usize, coord: Vector2 },
+    /// Every cell is collapsed.
+    Finished,
+}
+
+// A collapse decision that can be undone. Every ban made since `choice` was picked for
+// `coord` is at `trail[trail_len..]`, and `cells_to_collapse` is the count from right
+// before.
+struct Decision {
+    coord: Vector2,
+    choice: Id,
+    trail_len: usize,
+    cells_to_collapse: u32,
+}
+
+pub struct WaveFunctionOptions {
+    pub backtrack_depth: usize,
+    pub max_attempts: usize,
+    pub seed: u64,
+    /// how to pick the next cell to collapse
+    pub cell_selector: Box<dyn CellSelector>,
+    /// how to pick the tile a cell collapses into
+    pub tile_selector: Box<dyn TileSelector>,
+    /// whether neighbors wrap around the horizontal and vertical edges of the output
+    pub periodic_output: (bool, bool),
+}
+
+// The same defaults as the command line.
+impl Default for WaveFunctionOptions {
+    fn default() -> WaveFunctionOptions {
+        WaveFunctionOptions {
+            backtrack_depth: 0,
+            max_attempts: 10,
+            seed: 0,
+            cell_selector: Box::new(Entropy),
+            tile_selector: Box::new(WeightedRandom),
+            periodic_output: (false, false),
+        }
+    }
+}
+
+/// A collapsed wave: the tile picked for every cell, and the image they render to.
+pub struct Solution {
+    pub tile_ids: Grid<Id>,
+    pub image: Image,
+    /// how many times the wave was started over, including the one that succeeded
+    pub attempts: usize,
+    /// how many bans were propagated in the attempt that succeeded
+    pub iterations: usize,
+}
 
 pub struct WaveFunction {
     model: Model,
-    adjacency_rules: AdjacencyRules,
+    // every random decision is drawn from here, so a seed reproduces a run exactly
+    rng: StdRng,
+    cell_selector: Box<dyn CellSelector>,
+    tile_selector: Box<dyn TileSelector>,
+    // tile weights by id, used to compute entropy
+    weights: Vec<f64>,
+    propagator: Propagator,
     // wave data
-    state: HashMap<Vector2, CellState>,
+    state: Grid<CellState>,
+    // tiles removed from a cell whose removal hasn't been propagated yet
+    pending_bans: Vec<(Vector2, Id)>,
+    // cells (by grid index) that lost choices since the entropy heap was updated
+    changed_cells: BitSet,
+    // uncollapsed cells by `cell_selector.priority`, see `get_lowest_entropy_coord`
+    entropy_heap: EntropyHeap,
     dimensions: (u32, u32),
+    periodic_output: (bool, bool),
     cells_to_collapse: u32,
-    // gif related fields
-    make_gif: bool,
-    snapshots: Vec<Image>,
+    // backtracking and restart related fields
+    backtrack_depth: usize,
+    decisions: VecDeque<Decision>,
+    // every ban made since the oldest decision in `decisions`, in order
+    trail: Vec<(Vector2, Id)>,
+    max_attempts: usize,
+    // progress of the current attempt, see `WaveFunction::step`
+    started: bool,
+    attempts: usize,
+    iterations: usize,
+    // what happened since the last step returned
+    events: Vec<Event>,
+    // called with the number of collapsed cells and the total every time a cell
+    // collapses, see `WaveFunction::on_progress`
+    progress: Option<Box<dyn FnMut(u32, u32)>>,
 }
 
 impl WaveFunction {
@@ -33,33 +148,87 @@ impl WaveFunction {
         dimensions: (u32, u32),
         adjacency_rules: AdjacencyRules,
         model: Model,
-        make_gif: bool,
+        options: WaveFunctionOptions,
     ) -> WaveFunction {
         let (width, height) = dimensions;
-        let mut state = HashMap::new();
-        let choices = model.id_to_tile.keys().map(|id| *id).collect::<Vec<Id>>();
-        for y in 0..height {
-            for x in 0..width {
-                state.insert(
-                    Vector2 {
-                        x: x as i32,
-                        y: y as i32,
-                    },
-                    CellState {
-                        choices: choices.clone(),
-                        state: None,
-                    },
-                );
-            }
-        }
-        WaveFunction {
+        let weights = (0..model.id_to_tile.len())
+            .map(|id| model.id_to_frequency[&id] as f64)
+            .collect::<Vec<f64>>();
+        let propagator = Propagator::new(&adjacency_rules);
+        let initial_cell_state = CellState::new(
+            BitSet::full(model.id_to_tile.len()),
+            &weights,
+            propagator.initial_support(),
+            0.0,
+        );
+
+        let mut wave_function = WaveFunction {
             model,
-            adjacency_rules,
+            rng: StdRng::seed_from_u64(options.seed),
+            cell_selector: options.cell_selector,
+            tile_selector: options.tile_selector,
+            weights,
+            propagator,
+            state: Grid::from_fn(dimensions, |_| initial_cell_state.clone())
+                .with_periodic(options.periodic_output),
+            pending_bans: vec![],
+            changed_cells: BitSet::new((width * height) as usize),
+            entropy_heap: EntropyHeap::new(),
             dimensions,
-            state,
-            make_gif,
-            snapshots: vec![],
+            periodic_output: options.periodic_output,
             cells_to_collapse: width * height,
+            backtrack_depth: options.backtrack_depth,
+            decisions: VecDeque::new(),
+            trail: vec![],
+            max_attempts: options.max_attempts,
+            started: false,
+            attempts: 0,
+            iterations: 0,
+            events: vec![],
+            progress: None,
+        };
+        wave_function.reset();
+        wave_function
+    }
+
+    // Put the wave back into its initial, fully uncollapsed state. The model and
+    // adjacency rules are reused as-is.
+    fn reset(&mut self) {
+        let (width, height) = self.dimensions;
+        let choices = BitSet::full(self.model.id_to_tile.len());
+        let initial_cell_state = CellState::new(
+            choices,
+            &self.weights,
+            self.propagator.initial_support(),
+            0.0,
+        );
+
+        // in place, as a second grid of cells would double the memory the support
+        // counters take up
+        for cell_state in self.state.values_mut() {
+            cell_state.clone_from(&initial_cell_state);
+            cell_state.noise = self.rng.gen::<f64>() * ENTROPY_NOISE;
+        }
+        self.cells_to_collapse = width * height;
+        self.pending_bans.clear();
+        self.changed_cells.clear();
+        self.decisions.clear();
+        self.trail.clear();
+        self.started = false;
+        self.iterations = 0;
+        self.rebuild_entropy_heap();
+    }
+
+    fn rebuild_entropy_heap(&mut self) {
+        self.entropy_heap.clear();
+        for (coord, cell_state) in self.state.iter() {
+            if !cell_state.is_collapsed() {
+                self.entropy_heap.push(
+                    coord,
+                    self.cell_selector
+                        .priority(coord, cell_state, self.dimensions),
+                );
+            }
         }
     }
 
@@ -67,166 +236,412 @@ impl WaveFunction {
         self.cells_to_collapse == 0
     }
 
-    fn print_progress(&self) {
+    /// How many cells are collapsed, out of how many.
+    pub fn progress(&self) -> (u32, u32) {
         let (width, height) = self.dimensions;
         let area = width * height;
-        let ten_percent = area / 10;
+        (area - self.cells_to_collapse, area)
+    }
 
-        if self.cells_to_collapse % ten_percent == 0 {
-            println!(
-                "Progress: {}%",
-                (100 - (self.cells_to_collapse / ten_percent) * 10)
-            )
+    /// Call `progress` with the number of collapsed cells and the total number of cells
+    /// every time a cell collapses.
+    pub fn on_progress(&mut self, progress: impl FnMut(u32, u32) + 'static) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    fn report_progress(&mut self) {
+        let (collapsed, area) = self.progress();
+
+        if let Some(progress) = &mut self.progress {
+            progress(collapsed, area);
         }
     }
 
-    pub fn run(&mut self) -> Result<()> {
-        self.iterate()
+    /// Collapse the wave, restarting from scratch on contradictions until
+    /// `max_attempts` is used up.
+    pub fn run(&mut self) -> Result<Solution> {
+        self.run_observed(&mut [])
     }
 
-    fn iterate(&mut self) -> Result<()> {
-        let mut iterations = 0;
+    /// Like `run`, telling `observers` about every step along the way.
+    pub fn run_observed(&mut self, observers: &mut [&mut dyn Observer]) -> Result<Solution> {
+        while !self.is_finished() {
+            let events = self.step()?;
 
-        while !self.is_collapsed() {
-            let to_collapse = self.get_lowest_entropy_coord();
-            self.collapse(to_collapse)?;
-            iterations += self.propagate(to_collapse)?;
+            let mut bans = 0;
+            for event in &events {
+                match *event {
+                    Event::Collapsed { coord, tile } => {
+                        let (collapsed, total) = self.progress();
+                        for observer in observers.iter_mut() {
+                            observer.on_collapse(self, coord, tile);
+                            observer.on_progress(collapsed, total);
+                        }
+                    }
+                    Event::Banned { .. } => bans += 1,
+                    Event::Contradiction { coord } => {
+                        for observer in observers.iter_mut() {
+                            observer.on_contradiction(self, coord);
+                        }
+                    }
+                    Event::Restarted { attempt, coord } => {
+                        for observer in observers.iter_mut() {
+                            observer.on_restart(self, attempt, coord);
+                        }
+                    }
+                    Event::Finished => {}
+                }
+            }
+            for observer in observers.iter_mut() {
+                observer.on_propagate(self, bans);
+            }
+        }
+
+        let solution = self.solution().unwrap();
+        for observer in observers.iter_mut() {
+            observer.on_complete(self, &solution);
         }
+        Ok(solution)
+    }
 
-        println!("Iterations completed: {}", iterations);
+    /// Advance the wave by one step and return what happened in it. The first step
+    /// bans the tiles that can never be supported, and every step after that
+    /// collapses a single cell, each along with everything it propagates to. Once
+    /// the wave is collapsed, the last event is `Event::Finished` and further steps
+    /// do nothing.
+    pub fn step(&mut self) -> Result<Vec<Event>> {
+        if self.is_finished() {
+            return Ok(vec![]);
+        }
 
-        if self.make_gif {
-            // take final snapshot of state, then make the gif
-            self.take_snapshot();
-            GifBuilder::make_gif(&self.snapshots)
+        let outcome = if !self.started {
+            self.started = true;
+            self.attempts += 1;
+            self.ban_unsupported().and_then(|_| self.propagate())
         } else {
-            self.state_to_image().save("output")
+            let to_collapse = self.get_lowest_entropy_coord();
+            let choices = self.state[to_collapse].choices.clone();
+            let choice = self.choose_tile(&choices)?;
+            self.record_decision(to_collapse, choice);
+
+            self.collapse(to_collapse, choice)
+                .and_then(|_| self.propagate())
+        };
+
+        match outcome {
+            Ok(propagations) => self.iterations += propagations,
+            Err(contradiction) => {
+                if let Err(failure) = self.backtrack(contradiction) {
+                    let WaveFunctionError::Contradiction { coord } = failure else {
+                        return Err(failure.into());
+                    };
+                    if self.attempts >= self.max_attempts {
+                        return Err(WaveFunctionError::Unsatisfiable {
+                            attempts: self.attempts,
+                        }
+                        .into());
+                    }
+                    self.reset();
+                    self.events.push(Event::Restarted {
+                        attempt: self.attempts,
+                        coord,
+                    });
+                }
+            }
+        }
+
+        if self.is_finished() {
+            self.events.push(Event::Finished);
         }
+        Ok(mem::take(&mut self.events))
     }
 
-    fn collapse(&mut self, to_collapse: Vector2) -> Result<()> {
-        let choices = &self.state.get(&to_collapse).unwrap().get_choices();
-        let choice = self.get_random_choice(choices)?;
+    /// Every event of every step from here on, until the wave is collapsed or can't
+    /// be.
+    pub fn events(&mut self) -> Events<'_> {
+        Events {
+            wave_function: self,
+            pending: VecDeque::new(),
+            failed: false,
+        }
+    }
 
-        self.print_progress();
+    pub fn is_finished(&self) -> bool {
+        self.started && self.is_collapsed()
+    }
 
-        if self.should_take_snapshot() {
-            self.take_snapshot();
+    /// The collapsed wave, once it is finished.
+    pub fn solution(&self) -> Option<Solution> {
+        if !self.is_finished() {
+            return None;
         }
 
+        Some(Solution {
+            tile_ids: Grid::from_fn(self.dimensions, |coord| {
+                self.state[coord].choices.iter().next().unwrap()
+            }),
+            image: self.to_image(),
+            attempts: self.attempts,
+            iterations: self.iterations,
+        })
+    }
+
+    fn collapse(&mut self, to_collapse: Vector2, choice: Id) -> Result<(), WaveFunctionError> {
+        self.report_progress();
+        self.events.push(Event::Collapsed {
+            coord: to_collapse,
+            tile: choice,
+        });
+
         self.cells_to_collapse -= 1;
-        self.state.insert(
-            to_collapse,
-            CellState {
-                choices: vec![],
-                state: Some(choice),
-            },
-        );
+        // every other choice is banned rather than just dropped, so that the
+        // neighbors' support counters see the removal
+        let others = self.state[to_collapse].choices.clone();
+        for other in others.iter().filter(|other| *other != choice) {
+            self.ban(to_collapse, other)?;
+        }
+        self.state[to_collapse].collapse(choice);
+        Ok(())
+    }
 
+    // Remove `tile` from the choices of the cell at `coord` and queue the removal
+    // for `propagate`. Fails if that was the last choice left.
+    fn ban(&mut self, coord: Vector2, tile: Id) -> Result<(), WaveFunctionError> {
+        let cell_state = &mut self.state[coord];
+        cell_state.remove_choice(tile, self.weights[tile]);
+        self.pending_bans.push((coord, tile));
+        if !self.decisions.is_empty() {
+            self.trail.push((coord, tile));
+        }
+        self.changed_cells.insert(self.state.idx(coord));
+        self.events.push(Event::Banned { coord, tile });
+
+        if self.state[coord].choices.is_empty() {
+            self.events.push(Event::Contradiction { coord });
+            return Err(WaveFunctionError::Contradiction { coord });
+        }
         Ok(())
     }
 
-    fn should_take_snapshot(&self) -> bool {
-        self.make_gif && SNAPSHOT_COUNTER.fetch_add(1, Ordering::SeqCst) % GIF_SIZE_FACTOR == 0
+    // A tile that no tile at all may be placed at some offset from can never be
+    // picked in a cell that has a cell at the opposite offset.
+    fn ban_unsupported(&mut self) -> Result<(), WaveFunctionError> {
+        let (width, height) = self.dimensions;
+        let num_tiles = self.weights.len();
+        let initial_support = self.propagator.initial_support();
+
+        for idx in 0..(width * height) as usize {
+            let coord = self.state.coord(idx);
+            for k in 0..self.propagator.offsets().len() {
+                let offset = self.propagator.offsets()[k];
+                // the counters of a cell are kept from the point of view of the cell
+                // it is at `offset` from
+                if self.state.at_offset(coord, -offset).is_none() {
+                    continue;
+                }
+                for tile in 0..num_tiles {
+                    let support = initial_support[self.propagator.support_idx(tile, k)];
+                    if support == 0 && self.state[coord].choices.contains(tile) {
+                        self.ban(coord, tile)?;
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn propagate(&mut self, collapsed: Vector2) -> Result<usize> {
+    // Start remembering the bans that follow `choice` being collapsed into `coord`,
+    // so that they can be undone if the choice leads to a contradiction. Only the
+    // most recent `backtrack_depth` decisions are kept.
+    fn record_decision(&mut self, coord: Vector2, choice: Id) {
+        if self.backtrack_depth == 0 {
+            return;
+        }
+
+        if self.decisions.len() == self.backtrack_depth {
+            self.decisions.pop_front();
+            // the bans made before the oldest decision left can't be undone anymore
+            let forgotten = self
+                .decisions
+                .front()
+                .map_or(self.trail.len(), |decision| decision.trail_len);
+            self.trail.drain(..forgotten);
+            for decision in &mut self.decisions {
+                decision.trail_len -= forgotten;
+            }
+        }
+
+        self.decisions.push_back(Decision {
+            coord,
+            choice,
+            trail_len: self.trail.len(),
+            cells_to_collapse: self.cells_to_collapse,
+        });
+    }
+
+    // Undo decisions until the wave is consistent again. Each undone choice is
+    // banned from its cell and the ban is propagated; if that contradicts too, we
+    // keep unwinding. Fails once there are no decisions left to undo.
+    fn backtrack(&mut self, contradiction: WaveFunctionError) -> Result<(), WaveFunctionError> {
+        let mut contradiction = contradiction;
+
+        while let Some(decision) = self.decisions.pop_back() {
+            self.undo_bans(decision.trail_len);
+            self.cells_to_collapse = decision.cells_to_collapse;
+            self.state[decision.coord].state = None;
+            // the cell was popped off the entropy heap to be collapsed
+            self.changed_cells.insert(self.state.idx(decision.coord));
+
+            match self
+                .ban(decision.coord, decision.choice)
+                .and_then(|_| self.propagate())
+            {
+                Ok(_) => return Ok(()),
+                Err(next_contradiction) => contradiction = next_contradiction,
+            }
+        }
+
+        Err(contradiction)
+    }
+
+    // Undo the bans at `trail[trail_len..]`, most recent first: give each tile back to
+    // its cell along with the support its ban took away from the cells around it.
+    fn undo_bans(&mut self, trail_len: usize) {
+        // a ban that was never propagated took no support away
+        let unpropagated = self
+            .pending_bans
+            .drain(..)
+            .collect::<HashSet<(Vector2, Id)>>();
+
+        for (coord, tile) in self.trail.split_off(trail_len).into_iter().rev() {
+            if !unpropagated.contains(&(coord, tile)) {
+                for k in 0..self.propagator.offsets().len() {
+                    let offset = self.propagator.offsets()[k];
+                    let Some(neighbor) = self.state.at_offset(coord, offset) else {
+                        continue;
+                    };
+                    for other in self.propagator.compatible(tile, k) {
+                        self.state[neighbor].support[self.propagator.support_idx(*other, k)] += 1;
+                    }
+                }
+            }
+            self.state[coord].add_choice(tile, self.weights[tile]);
+            self.changed_cells.insert(self.state.idx(coord));
+        }
+    }
+
+    // Propagate every pending ban. Removing `tile` from a cell takes away one unit
+    // of support from each tile it was compatible with in the cells at each offset
+    // from it, and any tile left with no support at all is banned in turn. Returns
+    // how many bans were processed.
+    fn propagate(&mut self) -> Result<usize, WaveFunctionError> {
         let mut iterations = 0;
-        let mut stack = UniqueStack::from([collapsed]);
 
-        while !stack.is_empty() {
+        while let Some((coord, tile)) = self.pending_bans.pop() {
             iterations += 1;
-            if let Some(coord) = stack.pop() {
-                if let Some(cell_state) = self.state.get(&coord) {
-                    let choices = cell_state.get_choices();
-
-                    // For each neighbor, check if the cell we just collapsed affects
-                    // the choices in that neighbor. Specifically, if the neighbor has
-                    // any choices that still might work, then it's still fine.
-                    // Otherwise, remove that choice and add the neighbor to the stack.
-                    let neighbors = helpers::get_neighbors(self.dimensions, &coord);
-                    for (neighbor, direction) in &neighbors {
-                        let maybe_neighbor_state =
-                            self.state.get_mut(neighbor).filter(|cs| !cs.is_collapsed());
-                        if let Some(neighbor_state) = maybe_neighbor_state {
-                            let neighbor_choices = neighbor_state.get_choices();
-
-                            let mut add_neighbor = false;
-                            for neighbor_choice in &neighbor_choices {
-                                let is_valid = choices.iter().any(|choice| {
-                                    self.adjacency_rules.valid_neighbors(
-                                        *choice,
-                                        *neighbor_choice,
-                                        *direction,
-                                    )
-                                });
-
-                                if !is_valid {
-                                    neighbor_state.remove_choice(neighbor_choice);
-                                    add_neighbor = true;
-                                }
-                            }
-                            if add_neighbor {
-                                if neighbor_state.get_choices().len() == 1 {
-                                    self.collapse(neighbor.clone())?;
-                                }
-                                stack.push(*neighbor);
-                            }
-                        }
+
+            // all of the support is taken away before anything is banned, so that a
+            // contradiction never leaves a ban half propagated for `undo_bans`
+            let mut unsupported = vec![];
+            for k in 0..self.propagator.offsets().len() {
+                let offset = self.propagator.offsets()[k];
+                let Some(neighbor) = self.state.at_offset(coord, offset) else {
+                    continue;
+                };
+                let neighbor_state = &mut self.state[neighbor];
+
+                for other in self.propagator.compatible(tile, k) {
+                    let support =
+                        &mut neighbor_state.support[self.propagator.support_idx(*other, k)];
+                    *support -= 1;
+                    if *support == 0 && neighbor_state.choices.contains(*other) {
+                        unsupported.push((neighbor, *other));
                     }
                 }
             }
+
+            for (neighbor, other) in unsupported {
+                // on a small periodic grid, the same cell can be at two offsets
+                if self.state[neighbor].choices.contains(other) {
+                    self.ban(neighbor, other)?;
+                }
+            }
         }
+
+        for idx in self.changed_cells.iter() {
+            let coord = self.state.coord(idx);
+            let cell_state = &self.state[coord];
+            if !cell_state.is_collapsed() {
+                self.entropy_heap.push(
+                    coord,
+                    self.cell_selector
+                        .priority(coord, cell_state, self.dimensions),
+                );
+            }
+        }
+        self.changed_cells.clear();
+
         Ok(iterations)
     }
 
-    fn get_random_choice(&self, choices: &Vec<Id>) -> Result<Id> {
-        let mut rng = rand::thread_rng();
+    fn choose_tile(&mut self, choices: &BitSet) -> Result<Id> {
+        let choices = choices.iter().collect::<Vec<Id>>();
         let weights = choices
             .iter()
-            .flat_map(|id| self.model.frequency_hints.get(id))
-            .collect::<Vec<&f64>>();
-        let dist = WeightedIndex::new(weights)?;
-        Ok(choices[dist.sample(&mut rng)])
+            .map(|id| self.model.frequency_hints[id])
+            .collect::<Vec<f64>>();
+        self.tile_selector.select(&choices, &weights, &mut self.rng)
     }
 
-    fn get_lowest_entropy_coord(&self) -> Vector2 {
-        let mut choices = self
-            .state
-            .iter()
-            .filter(|(_, cell_state)| !cell_state.is_collapsed())
-            .map(|(coord, cell_state)| (*coord, cell_state.get_choices().len()))
-            .collect::<Vec<(Vector2, usize)>>();
-
-        choices.sort_by(|(_, choices_a), (_, choices_b)| choices_a.cmp(choices_b));
-
-        let (coord, _) = choices.first().unwrap();
-        *coord
+    // Pop cells off the heap until one is found that is still uncollapsed and whose
+    // entry is up to date. Any entry that doesn't match the cell's current priority
+    // was superseded by a later push when the cell lost choices.
+    fn get_lowest_entropy_coord(&mut self) -> Vector2 {
+        while let Some((coord, priority)) = self.entropy_heap.pop() {
+            let cell_state = &self.state[coord];
+            if !cell_state.is_collapsed()
+                && self
+                    .cell_selector
+                    .priority(coord, cell_state, self.dimensions)
+                    == priority
+            {
+                return coord;
+            }
+        }
+        panic!("Every uncollapsed cell should have an entry in the entropy heap")
     }
 
-    fn state_to_image(&self) -> Image {
+    /// The wave as it is, with the tiles still possible in each cell blended together.
+    pub fn to_image(&self) -> Image {
         match self.model.mode {
             Mode::Overlap => self.overlap_state_to_image(),
-            Mode::Tile => self.tiled_state_to_image(),
+            Mode::Tile | Mode::TileSet => self.tiled_state_to_image(),
         }
     }
 
+    // Every pixel is read from a pattern that covers it: the one whose top left
+    // corner it is, or, past the last cell of an axis that doesn't wrap around, the
+    // last one along that axis. On a periodic axis the patterns of the last cells
+    // wrap around onto the first ones, so the image repeats without seams.
     fn overlap_state_to_image(&self) -> Image {
-        let (width, height) = self.dimensions;
+        let (width, height) = self
+            .model
+            .output_dimensions(self.dimensions, self.periodic_output);
         let mut img = Image::new(width, height);
 
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = Vector2 {
-                    x: x as i32,
-                    y: y as i32,
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let pixel = Vector2 { x, y };
+                let cell = Vector2 {
+                    x: x.min(self.dimensions.0 as i32 - 1),
+                    y: y.min(self.dimensions.1 as i32 - 1),
                 };
-                let state = &self.state[&pixel];
-                let color = state
-                    .get_choices()
+                let (t_x, t_y) = ((x - cell.x) as u32, (y - cell.y) as u32);
+
+                let color = self.state[cell]
+                    .choices
                     .iter()
-                    .map(|id| &self.model.id_to_tile[id])
-                    .map(|t| t.pixels[0].clone()) // take the top left pixel from the tile
+                    .map(|id| self.model.id_to_tile[&id].at(t_x, t_y))
                     .reduce(|l, r| l.blend(&r)) // blend all the pixels together
                     .unwrap();
 
@@ -242,35 +657,482 @@ impl WaveFunction {
         let (tile_width, tile_height) = self.model.tile_dimensions;
         let mut img = Image::new(width * tile_width, height * tile_height);
 
-        for y in 0..height {
-            for x in 0..width {
-                let coord = Vector2 {
-                    x: x as i32,
-                    y: y as i32,
-                };
-                let tile = &self.state[&coord]
-                    .get_choices()
-                    .iter()
-                    .map(|id| &self.model.id_to_tile[id])
-                    .map(|t| t.clone())
-                    .reduce(|l, r| l.blend(r))
-                    .unwrap();
+        for (coord, state) in self.state.iter() {
+            let tile = state
+                .choices
+                .iter()
+                .map(|id| &self.model.id_to_tile[&id])
+                .cloned()
+                .reduce(|l, r| l.blend(r))
+                .unwrap();
 
-                for t_y in 0..tile_height {
-                    for t_x in 0..tile_width {
-                        let pixel = Vector2 {
-                            x: (x * tile_width + t_x) as i32,
-                            y: (y * tile_height + t_y) as i32,
-                        };
-                        img.set_color(pixel, tile.at(t_x, t_y));
-                    }
+            for t_y in 0..tile_height {
+                for t_x in 0..tile_width {
+                    let pixel = Vector2 {
+                        x: coord.x * tile_width as i32 + t_x as i32,
+                        y: coord.y * tile_height as i32 + t_y as i32,
+                    };
+                    img.set_color(pixel, tile.at(t_x, t_y));
                 }
             }
         }
         img
     }
+}
+
+/// The events of `WaveFunction::step`, one at a time. Stepping stops after
+/// `Event::Finished`, or after the error that ended the run.
+pub struct Events<'a> {
+    wave_function: &'a mut WaveFunction,
+    pending: VecDeque<Event>,
+    failed: bool,
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.failed || self.wave_function.is_finished() {
+                return None;
+            }
+
+            match self.wave_function.step() {
+                Ok(events) => self.pending.extend(events),
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::adjacency_rules::AdjacencyRules;
+    use crate::cell_selector::{Entropy, Scanline};
+    use crate::data::{bitset::BitSet, direction::Direction};
+    use crate::data::{id::Id, vector2::Vector2};
+    use crate::data::{mode::Mode, symmetry::Symmetry, tile::Tile};
+    use crate::gif_builder::GifRecorder;
+    use crate::model::Model;
+    use crate::observer::Observer;
+    use crate::tile_selector::WeightedRandom;
+
+    use super::{Event, Solution, WaveFunction, WaveFunctionError, WaveFunctionOptions};
+
+    // Three single-pixel tiles. 1 needs a 2 to its right and below it, while 2 needs
+    // a 1 to its right and a 0 below it; 0 goes anywhere else. Every tile has some
+    // neighbor it may sit next to, so nothing is ruled out up front, but a 1 away
+    // from the right and bottom edges is a contradiction: the cell diagonally below
+    // it would have to be both 1 and 0.
+    fn mk_model_and_rules() -> (Model, AdjacencyRules) {
+        let tiles = [[0, 0, 0], [255, 0, 0], [0, 0, 255]].map(|pixel| Tile {
+            width: 1,
+            height: 1,
+            pixels: vec![pixel],
+        });
+        let model = Model {
+            mode: Mode::Overlap,
+            tile_dimensions: (1, 1),
+            tile_to_positions: HashMap::new(),
+            position_to_tile: HashMap::new(),
+            id_to_tile: tiles.iter().cloned().enumerate().collect(),
+            tile_to_id: tiles
+                .into_iter()
+                .enumerate()
+                .map(|(id, t)| (t, id))
+                .collect(),
+            id_to_frequency: HashMap::from([(0, 1), (1, 1), (2, 1)]),
+            frequency_hints: HashMap::from([(0, 1.0 / 3.0), (1, 1.0 / 3.0), (2, 1.0 / 3.0)]),
+            id_to_symmetry: HashMap::new(),
+            symmetry: Symmetry::None,
+            id_to_name: HashMap::new(),
+        };
+
+        let forced = [
+            (1, Direction::RIGHT, 2),
+            (1, Direction::DOWN, 2),
+            (2, Direction::RIGHT, 1),
+            (2, Direction::DOWN, 0),
+        ];
+        let mut rules = AdjacencyRules::new(3);
+        let offsets = rules.offsets.clone();
+        for a in 0..3 {
+            for b in 0..3 {
+                for (k, offset) in offsets.iter().enumerate() {
+                    // 'b' is at `offset` from 'a', and 'a' at `-offset` from 'b'
+                    let breaks = |t, offset, x| {
+                        forced.iter().any(|(forced_t, d, forced_x)| {
+                            *forced_t == t && offsets[d.idx()] == offset && *forced_x != x
+                        })
+                    };
+                    if !breaks(a, *offset, b) && !breaks(b, -*offset, a) {
+                        rules.rules[a][b][k] = true;
+                    }
+                }
+            }
+        }
+
+        (model, rules)
+    }
+
+    // Every cell is collapsed and agrees with all of its neighbors, including the ones
+    // across a periodic edge.
+    fn assert_valid(wave_function: &WaveFunction) {
+        let (_, rules) = mk_model_and_rules();
+        let state = &wave_function.state;
+        for (coord, cell_state) in state.iter() {
+            let tile = cell_state.state.unwrap();
+            for (k, offset) in rules.offsets.iter().enumerate() {
+                if let Some(neighbor) = state.at_offset(coord, *offset) {
+                    let neighbor_tile = state[neighbor].state.unwrap();
+                    assert!(rules.rules[tile][neighbor_tile][k]);
+                }
+            }
+        }
+    }
+
+    fn mk_options(backtrack_depth: usize, max_attempts: usize, seed: u64) -> WaveFunctionOptions {
+        WaveFunctionOptions {
+            backtrack_depth,
+            max_attempts,
+            seed,
+            cell_selector: Box::new(Entropy),
+            tile_selector: Box::new(WeightedRandom),
+            periodic_output: (false, false),
+        }
+    }
+
+    #[test]
+    fn test_backtracking_recovers_from_contradictions() {
+        for seed in 0..20 {
+            let (model, rules) = mk_model_and_rules();
+            let mut wave_function =
+                WaveFunction::new((4, 4), rules, model, mk_options(16, 1, seed));
+
+            assert!(wave_function.run().is_ok());
+            assert_valid(&wave_function);
+        }
+    }
+
+    #[test]
+    fn test_undoing_bans_restores_the_wave() {
+        let (model, rules) = mk_model_and_rules();
+        let mut wave_function = WaveFunction::new((4, 4), rules, model, mk_options(16, 1, 0));
+        wave_function.step().unwrap();
+        let initial = wave_function.state.clone();
+
+        for (coord, _) in initial.iter() {
+            for choice in 0..3 {
+                // a 1 away from the right and bottom edges contradicts while propagating,
+                // with some bans left unpropagated
+                wave_function.record_decision(coord, choice);
+                let _ = wave_function
+                    .collapse(coord, choice)
+                    .and_then(|_| wave_function.propagate());
+
+                let decision = wave_function.decisions.pop_back().unwrap();
+                wave_function.undo_bans(decision.trail_len);
+                wave_function.state[coord].state = None;
+                wave_function.cells_to_collapse = decision.cells_to_collapse;
+
+                for (coord, cell_state) in wave_function.state.iter() {
+                    assert_eq!(cell_state.choices, initial[coord].choices);
+                    assert_eq!(cell_state.support, initial[coord].support);
+                    assert_eq!(cell_state.state, None);
+                    assert!((cell_state.entropy() - initial[coord].entropy()).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_periodic_output_wraps_around() {
+        for seed in 0..20 {
+            let (model, rules) = mk_model_and_rules();
+            let mut options = mk_options(16, 1, seed);
+            options.periodic_output = (true, true);
+            let mut wave_function = WaveFunction::new((4, 4), rules, model, options);
+
+            // a 1 is a contradiction wherever it goes once there are no edges
+            assert!(wave_function.run().is_ok());
+            assert!(wave_function
+                .state
+                .iter()
+                .all(|(_, cell_state)| cell_state.state == Some(0)));
+            assert_valid(&wave_function);
+        }
+    }
 
-    fn take_snapshot(&mut self) {
-        self.snapshots.push(self.state_to_image());
+    #[test]
+    fn test_overlap_pixels_come_from_the_pattern_covering_them() {
+        // four 2x2 patterns, whose pixels tell apart the pattern and where in it they are
+        let color = |id: usize, x: u32, y: u32| [id as u8 * 60, x as u8 * 100, y as u8 * 100];
+        let tiles = (0..4).map(|id| Tile {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                color(id, 0, 0),
+                color(id, 1, 0),
+                color(id, 0, 1),
+                color(id, 1, 1),
+            ],
+        });
+        let model = Model {
+            mode: Mode::Overlap,
+            tile_dimensions: (2, 2),
+            tile_to_positions: HashMap::new(),
+            position_to_tile: HashMap::new(),
+            id_to_tile: tiles.clone().enumerate().collect(),
+            tile_to_id: tiles.enumerate().map(|(id, t)| (t, id)).collect(),
+            id_to_frequency: HashMap::from([(0, 1), (1, 1), (2, 1), (3, 1)]),
+            frequency_hints: HashMap::from([(0, 0.25), (1, 0.25), (2, 0.25), (3, 0.25)]),
+            id_to_symmetry: HashMap::new(),
+            symmetry: Symmetry::None,
+            id_to_name: HashMap::new(),
+        };
+
+        for periodic_output in [(false, false), (true, false), (true, true)] {
+            let mut options = mk_options(0, 1, 0);
+            options.periodic_output = periodic_output;
+            let mut wave_function =
+                WaveFunction::new((2, 2), AdjacencyRules::new(4), model.clone(), options);
+            // the cells hold patterns 0 and 1 in the top row, 2 and 3 in the bottom one
+            for y in 0..2 {
+                for x in 0..2 {
+                    let mut choices = BitSet::new(4);
+                    choices.insert((y * 2 + x) as usize);
+                    wave_function.state[Vector2 { x, y }].choices = choices;
+                }
+            }
+
+            let image = wave_function.to_image();
+            let (width, height) = model.output_dimensions((2, 2), periodic_output);
+            assert_eq!((image.width, image.height), (width, height));
+            for y in 0..height as i32 {
+                for x in 0..width as i32 {
+                    // a periodic axis only has the cells' own top left pixels, while the
+                    // last pixels of any other axis come from the last cell's pattern
+                    let cell = Vector2 {
+                        x: x.min(1),
+                        y: y.min(1),
+                    };
+                    let id = (cell.y * 2 + cell.x) as usize;
+                    let expected = color(id, (x - cell.x) as u32, (y - cell.y) as u32);
+                    assert_eq!(image.at(Vector2 { x, y }), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_tiles_are_banned_up_front() {
+        // 2 may no longer sit below anything, so it can only go in the top row
+        let (model, mut rules) = mk_model_and_rules();
+        for a in 0..3 {
+            rules.rules[a][2][Direction::DOWN.idx()] = false;
+        }
+        let mut wave_function = WaveFunction::new((4, 4), rules, model, mk_options(0, 1, 0));
+        wave_function.ban_unsupported().unwrap();
+
+        for (coord, cell_state) in wave_function.state.iter() {
+            assert_eq!(cell_state.choices.contains(2), coord.y == 0);
+        }
+    }
+
+    #[test]
+    fn test_contradiction_without_backtracking() {
+        // with no decisions to undo, the first contradiction fails the run
+        let failed = (0..20).any(|seed| {
+            let (model, rules) = mk_model_and_rules();
+            WaveFunction::new((4, 4), rules, model, mk_options(0, 1, seed))
+                .run()
+                .is_err()
+        });
+
+        assert!(failed);
+    }
+
+    #[test]
+    fn test_restart_until_solved() {
+        let (model, rules) = mk_model_and_rules();
+        let mut wave_function = WaveFunction::new((4, 4), rules, model, mk_options(0, 64, 0));
+
+        assert!(wave_function.run().is_ok());
+    }
+
+    #[test]
+    fn test_observers_are_told_about_restarts() {
+        let (model, rules) = mk_model_and_rules();
+        let mut wave_function = WaveFunction::new((4, 4), rules, model, mk_options(0, 64, 0));
+        let mut counter = Counter::default();
+
+        let solution = wave_function.run_observed(&mut [&mut counter]).unwrap();
+
+        // every attempt but the last ended in a contradiction
+        assert!(solution.attempts > 1);
+        assert_eq!(
+            counter.restarts,
+            (1..solution.attempts).collect::<Vec<usize>>()
+        );
+        assert_eq!(counter.contradictions, solution.attempts - 1);
+    }
+
+    #[test]
+    fn test_unsatisfiable_after_max_attempts() {
+        // no tile may sit next to anything
+        let (model, mut rules) = mk_model_and_rules();
+        rules.rules = vec![vec![vec![false; 4]; 3]; 3];
+        let mut wave_function = WaveFunction::new((4, 4), rules, model, mk_options(0, 3, 0));
+
+        let Err(err) = wave_function.run() else {
+            panic!("an unsatisfiable wave was solved");
+        };
+        assert!(matches!(
+            err.downcast_ref::<WaveFunctionError>(),
+            Some(WaveFunctionError::Unsatisfiable { attempts: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_same_seed_same_wave() {
+        let collapse = |seed| {
+            let (model, mut rules) = mk_model_and_rules();
+            // let every tile sit anywhere so that every cell is a real choice
+            rules.rules = vec![vec![vec![true; 4]; 3]; 3];
+            let mut wave_function = WaveFunction::new((8, 8), rules, model, mk_options(0, 1, seed));
+            wave_function.run().unwrap();
+            wave_function.to_image().pixels
+        };
+
+        assert_eq!(collapse(1234), collapse(1234));
+        assert_ne!(collapse(1234), collapse(4321));
+    }
+
+    #[test]
+    fn test_events_follow_the_collapse() {
+        let mk_wave_function = || {
+            let (model, mut rules) = mk_model_and_rules();
+            rules.rules = vec![vec![vec![true; 4]; 3]; 3];
+            WaveFunction::new((3, 3), rules, model, mk_options(0, 1, 42))
+        };
+
+        let mut stepped = mk_wave_function();
+        let events = stepped
+            .events()
+            .collect::<anyhow::Result<Vec<Event>>>()
+            .unwrap();
+        assert_eq!(events.last(), Some(&Event::Finished));
+        assert!(stepped.step().unwrap().is_empty());
+
+        // every cell is collapsed once, into the tile it ends up with
+        let solution = stepped.solution().unwrap();
+        let collapsed = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Collapsed { coord, tile } => Some((*coord, *tile)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(collapsed.len(), 9);
+        for (coord, tile) in collapsed {
+            assert_eq!(solution.tile_ids[coord], tile);
+        }
+
+        // stepping doesn't change the outcome
+        let run = mk_wave_function().run().unwrap();
+        assert_eq!(run.image.pixels, solution.image.pixels);
+    }
+
+    // Counts what it is told about.
+    #[derive(Default)]
+    struct Counter {
+        collapses: usize,
+        bans: usize,
+        last_progress: (u32, u32),
+        contradictions: usize,
+        restarts: Vec<usize>,
+        completions: usize,
+    }
+
+    impl Observer for Counter {
+        fn on_collapse(&mut self, _: &WaveFunction, _: Vector2, _: Id) {
+            self.collapses += 1;
+        }
+
+        fn on_propagate(&mut self, _: &WaveFunction, bans: usize) {
+            self.bans += bans;
+        }
+
+        fn on_progress(&mut self, collapsed: u32, total: u32) {
+            self.last_progress = (collapsed, total);
+        }
+
+        fn on_contradiction(&mut self, _: &WaveFunction, _: Vector2) {
+            self.contradictions += 1;
+        }
+
+        fn on_restart(&mut self, _: &WaveFunction, attempt: usize, _: Vector2) {
+            self.restarts.push(attempt);
+        }
+
+        fn on_complete(&mut self, _: &WaveFunction, _: &Solution) {
+            self.completions += 1;
+        }
+    }
+
+    #[test]
+    fn test_observers_are_told_about_each_wave() {
+        let mk_wave_function = || {
+            let (model, mut rules) = mk_model_and_rules();
+            rules.rules = vec![vec![vec![true; 4]; 3]; 3];
+            WaveFunction::new((5, 5), rules, model, mk_options(0, 1, 7))
+        };
+
+        let mut counter = Counter::default();
+        let (mut first, mut second) = (GifRecorder::default(), GifRecorder::default());
+        mk_wave_function()
+            .run_observed(&mut [&mut counter, &mut first])
+            .unwrap();
+        mk_wave_function().run_observed(&mut [&mut second]).unwrap();
+
+        assert_eq!(counter.collapses, 25);
+        // every collapse bans the two other tiles, and nothing else is ruled out
+        assert_eq!(counter.bans, 50);
+        assert_eq!(counter.last_progress, (25, 25));
+        assert_eq!(counter.contradictions, 0);
+        assert!(counter.restarts.is_empty());
+        assert_eq!(counter.completions, 1);
+        // one snapshot every 10 collapses, plus the final one, for each wave
+        assert_eq!(first.snapshots.len(), 4);
+        assert_eq!(second.snapshots.len(), 4);
+    }
+
+    #[test]
+    fn test_cell_selector_picks_the_order() {
+        let (model, mut rules) = mk_model_and_rules();
+        rules.rules = vec![vec![vec![true; 4]; 3]; 3];
+        let mut options = mk_options(0, 1, 0);
+        options.cell_selector = Box::new(Scanline);
+        let mut wave_function = WaveFunction::new((3, 2), rules, model, options);
+
+        let collapsed = wave_function
+            .events()
+            .filter_map(|event| match event.unwrap() {
+                Event::Collapsed { coord, .. } => Some((coord.x, coord.y)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            collapsed,
+            vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]
+        );
     }
 }